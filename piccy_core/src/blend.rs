@@ -0,0 +1,312 @@
+use crate::BlendMode;
+use image::Rgba;
+
+type Color = [f32; 3];
+
+/// 按混合模式计算混合颜色，颜色分量取值 0.0 ~ 1.0
+///
+/// 公式参考 W3C Compositing and Blending Level 1。
+pub(crate) fn blend(mode: BlendMode, backdrop: Color, source: Color) -> Color {
+    match mode {
+        BlendMode::Normal => source,
+        BlendMode::Multiply => separable(backdrop, source, |b, s| b * s),
+        BlendMode::Screen => separable(backdrop, source, screen),
+        BlendMode::Overlay => separable(backdrop, source, |b, s| hard_light(s, b)),
+        BlendMode::Darken => separable(backdrop, source, f32::min),
+        BlendMode::Lighten => separable(backdrop, source, f32::max),
+        BlendMode::ColorDodge => separable(backdrop, source, |b, s| {
+            if b == 0.0 {
+                0.0
+            } else if s >= 1.0 {
+                1.0
+            } else {
+                (b / (1.0 - s)).min(1.0)
+            }
+        }),
+        BlendMode::ColorBurn => separable(backdrop, source, |b, s| {
+            if b >= 1.0 {
+                1.0
+            } else if s == 0.0 {
+                0.0
+            } else {
+                1.0 - ((1.0 - b) / s).min(1.0)
+            }
+        }),
+        BlendMode::HardLight => separable(backdrop, source, hard_light),
+        BlendMode::SoftLight => separable(backdrop, source, |b, s| {
+            if s <= 0.5 {
+                b - (1.0 - 2.0 * s) * b * (1.0 - b)
+            } else {
+                let d = if b <= 0.25 {
+                    ((16.0 * b - 12.0) * b + 4.0) * b
+                } else {
+                    b.sqrt()
+                };
+                b + (2.0 * s - 1.0) * (d - b)
+            }
+        }),
+        BlendMode::Difference => separable(backdrop, source, |b, s| (b - s).abs()),
+        BlendMode::Exclusion => separable(backdrop, source, |b, s| b + s - 2.0 * b * s),
+        BlendMode::Hue => set_lum(set_sat(source, sat(backdrop)), lum(backdrop)),
+        BlendMode::Saturation => set_lum(set_sat(backdrop, sat(source)), lum(backdrop)),
+        BlendMode::Color => set_lum(source, lum(backdrop)),
+        BlendMode::Luminosity => set_lum(backdrop, lum(source)),
+    }
+}
+
+/// 将源像素按混合模式和不透明度合成到底图像素上
+///
+/// # 参数
+/// - `backdrop`: 底图像素
+/// - `source`: 源像素
+/// - `opacity`: 源像素的额外不透明度，取值 0.0 ~ 1.0
+/// - `mode`: 混合模式
+pub(crate) fn composite_pixel(
+    backdrop: &mut Rgba<u8>,
+    source: Rgba<u8>,
+    opacity: f32,
+    mode: BlendMode,
+) {
+    let source_alpha = source[3] as f32 / 255.0 * opacity;
+    if source_alpha <= 0.0 {
+        return;
+    }
+    let backdrop_alpha = backdrop[3] as f32 / 255.0;
+    let alpha = source_alpha + backdrop_alpha * (1.0 - source_alpha);
+
+    let backdrop_color = normalize(*backdrop);
+    let source_color = normalize(source);
+    let blended = blend(mode, backdrop_color, source_color);
+    for channel in 0..3 {
+        // 底图不透明的部分使用混合颜色，其余部分保留源颜色
+        let mixed =
+            (1.0 - backdrop_alpha) * source_color[channel] + backdrop_alpha * blended[channel];
+        let value = (mixed * source_alpha
+            + backdrop_color[channel] * backdrop_alpha * (1.0 - source_alpha))
+            / alpha;
+        backdrop[channel] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+    backdrop[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+}
+
+/// 将颜色按混合模式和不透明度混合到像素上，像素的透明度保持不变
+///
+/// # 参数
+/// - `backdrop`: 底图像素
+/// - `color`: 混合的颜色
+/// - `opacity`: 颜色的额外不透明度，取值 0.0 ~ 1.0
+/// - `mode`: 混合模式
+pub(crate) fn tint_pixel(backdrop: &mut Rgba<u8>, color: Rgba<u8>, opacity: f32, mode: BlendMode) {
+    let source_alpha = color[3] as f32 / 255.0 * opacity;
+    if source_alpha <= 0.0 || backdrop[3] == 0 {
+        return;
+    }
+    let backdrop_color = normalize(*backdrop);
+    let blended = blend(mode, backdrop_color, normalize(color));
+    for channel in 0..3 {
+        let value =
+            blended[channel] * source_alpha + backdrop_color[channel] * (1.0 - source_alpha);
+        backdrop[channel] = (value * 255.0).round().clamp(0.0, 255.0) as u8;
+    }
+}
+
+fn normalize(pixel: Rgba<u8>) -> Color {
+    let [r, g, b, _] = pixel.0;
+    [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+}
+
+fn separable(backdrop: Color, source: Color, f: impl Fn(f32, f32) -> f32) -> Color {
+    [
+        f(backdrop[0], source[0]),
+        f(backdrop[1], source[1]),
+        f(backdrop[2], source[2]),
+    ]
+}
+
+fn screen(backdrop: f32, source: f32) -> f32 {
+    backdrop + source - backdrop * source
+}
+
+fn hard_light(backdrop: f32, source: f32) -> f32 {
+    if source <= 0.5 {
+        backdrop * 2.0 * source
+    } else {
+        screen(backdrop, 2.0 * source - 1.0)
+    }
+}
+
+fn lum([r, g, b]: Color) -> f32 {
+    0.3 * r + 0.59 * g + 0.11 * b
+}
+
+fn clip_color(color: Color) -> Color {
+    let l = lum(color);
+    let n = color[0].min(color[1]).min(color[2]);
+    let x = color[0].max(color[1]).max(color[2]);
+    color.map(|c| {
+        let mut c = c;
+        if n < 0.0 {
+            c = l + (c - l) * l / (l - n);
+        }
+        if x > 1.0 {
+            c = l + (c - l) * (1.0 - l) / (x - l);
+        }
+        c
+    })
+}
+
+fn set_lum(color: Color, l: f32) -> Color {
+    let d = l - lum(color);
+    clip_color(color.map(|c| c + d))
+}
+
+fn sat([r, g, b]: Color) -> f32 {
+    r.max(g).max(b) - r.min(g).min(b)
+}
+
+fn set_sat(color: Color, s: f32) -> Color {
+    let max = color[0].max(color[1]).max(color[2]);
+    let min = color[0].min(color[1]).min(color[2]);
+    if max <= min {
+        return [0.0; 3];
+    }
+    color.map(|c| {
+        if c == max {
+            s
+        } else if c == min {
+            0.0
+        } else {
+            (c - min) * s / (max - min)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: Color, expected: Color) {
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-5, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn separable_modes() {
+        let backdrop = [0.2, 0.5, 1.0];
+        let source = [0.6, 0.5, 0.0];
+        assert_close(blend(BlendMode::Normal, backdrop, source), source);
+        assert_close(
+            blend(BlendMode::Multiply, backdrop, source),
+            [0.12, 0.25, 0.0],
+        );
+        assert_close(
+            blend(BlendMode::Screen, backdrop, source),
+            [0.68, 0.75, 1.0],
+        );
+        assert_close(blend(BlendMode::Darken, backdrop, source), [0.2, 0.5, 0.0]);
+        assert_close(blend(BlendMode::Lighten, backdrop, source), [0.6, 0.5, 1.0]);
+        assert_close(
+            blend(BlendMode::Difference, backdrop, source),
+            [0.4, 0.0, 1.0],
+        );
+        assert_close(
+            blend(BlendMode::Exclusion, backdrop, source),
+            [0.56, 0.5, 1.0],
+        );
+        // 叠加是交换了参数的强光
+        assert_close(
+            blend(BlendMode::HardLight, backdrop, source),
+            [0.36, 0.5, 0.0],
+        );
+        assert_close(
+            blend(BlendMode::Overlay, backdrop, source),
+            [0.24, 0.5, 1.0],
+        );
+    }
+
+    #[test]
+    fn dodge_burn_and_soft_light() {
+        assert_close(
+            blend(BlendMode::ColorDodge, [0.0, 0.5, 0.25], [0.5, 1.0, 0.5]),
+            [0.0, 1.0, 0.5],
+        );
+        assert_close(
+            blend(BlendMode::ColorBurn, [1.0, 0.5, 0.75], [0.5, 0.0, 0.5]),
+            [1.0, 0.0, 0.5],
+        );
+        // 源颜色为 0.5 时柔光不改变底色
+        assert_close(
+            blend(BlendMode::SoftLight, [0.1, 0.3, 0.8], [0.5; 3]),
+            [0.1, 0.3, 0.8],
+        );
+        assert_close(
+            blend(BlendMode::SoftLight, [0.25, 0.5, 0.25], [1.0, 0.0, 0.0]),
+            [0.5, 0.25, 0.0625],
+        );
+    }
+
+    #[test]
+    fn non_separable_modes_keep_luminosity() {
+        let backdrop = [0.4, 0.3, 0.2];
+        let source = [0.1, 0.5, 0.6];
+        for mode in [BlendMode::Hue, BlendMode::Saturation, BlendMode::Color] {
+            let result = blend(mode, backdrop, source);
+            assert!((lum(result) - lum(backdrop)).abs() < 1e-5, "{mode:?}");
+        }
+        let result = blend(BlendMode::Luminosity, backdrop, source);
+        assert!((lum(result) - lum(source)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn composite_normal() {
+        let mut pixel = Rgba([0, 0, 0, 255]);
+        composite_pixel(
+            &mut pixel,
+            Rgba([255, 255, 255, 255]),
+            0.5,
+            BlendMode::Normal,
+        );
+        assert_eq!(pixel, Rgba([128, 128, 128, 255]));
+
+        // 底图透明时保留源颜色
+        let mut pixel = Rgba([0, 0, 0, 0]);
+        composite_pixel(
+            &mut pixel,
+            Rgba([10, 20, 30, 128]),
+            1.0,
+            BlendMode::Multiply,
+        );
+        assert_eq!(pixel, Rgba([10, 20, 30, 128]));
+
+        let mut pixel = Rgba([200, 100, 50, 255]);
+        composite_pixel(
+            &mut pixel,
+            Rgba([255, 255, 255, 255]),
+            1.0,
+            BlendMode::Multiply,
+        );
+        assert_eq!(pixel, Rgba([200, 100, 50, 255]));
+    }
+
+    #[test]
+    fn tint_keeps_alpha() {
+        let mut pixel = Rgba([10, 20, 30, 100]);
+        tint_pixel(
+            &mut pixel,
+            Rgba([255, 255, 255, 255]),
+            1.0,
+            BlendMode::Normal,
+        );
+        assert_eq!(pixel, Rgba([255, 255, 255, 100]));
+
+        let mut pixel = Rgba([10, 20, 30, 0]);
+        tint_pixel(
+            &mut pixel,
+            Rgba([255, 255, 255, 255]),
+            1.0,
+            BlendMode::Normal,
+        );
+        assert_eq!(pixel, Rgba([10, 20, 30, 0]));
+    }
+}
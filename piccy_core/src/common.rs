@@ -1,14 +1,818 @@
+use crate::blend::composite_pixel;
+use crate::error::Error;
+use crate::parallel::install;
+use crate::task::Task;
+use crate::{BlendMode, Fill, Interpolation};
 use bytes::Bytes;
-use image::codecs::gif::Repeat;
-use image::Frame;
+use image::codecs::gif::{GifDecoder, Repeat};
+use image::codecs::webp::WebPDecoder;
+use image::error::{ImageError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::imageops::FilterType;
+use image::{AnimationDecoder, Frame, Frames, GrayImage, Luma, RgbImage, Rgba, RgbaImage};
+use imageproc::geometric_transformations::{Border, Projection, warp_into_with};
+use std::io::Cursor;
 
+/// 生成画布的最大字节数，与 image 默认的 512 MiB 解码内存上限保持一致
+pub(crate) const MAX_CANVAS_BYTES: u64 = 512 * 1024 * 1024;
 
-pub(crate) fn encode_gif(frames: Vec<Frame>) -> crate::Result<Bytes> {
+/// 判断 RGBA 画布的大小是否在 [`MAX_CANVAS_BYTES`] 以内
+pub(crate) fn canvas_fits(width: u64, height: u64) -> bool {
+    width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .is_some_and(|bytes| bytes <= MAX_CANVAS_BYTES)
+}
+
+/// 逐帧编码 GIF
+///
+/// 每一帧在写入编码器后立即释放，峰值内存与帧数无关。
+pub(crate) fn encode_gif_stream<I>(frames: I) -> crate::Result<Bytes>
+where
+    I: IntoIterator<Item = crate::Result<Frame>>,
+{
     let mut buffer = Vec::new();
     {
         let mut encoder = image::codecs::gif::GifEncoder::new(&mut buffer);
         encoder.set_repeat(Repeat::Infinite)?;
-        encoder.encode_frames(frames)?;
+        for frame in frames {
+            encoder.encode_frame(frame?)?;
+        }
     }
     Ok(buffer.into())
 }
+
+/// 逐帧同时编码多个 GIF
+///
+/// 迭代器的每一项包含各个 GIF 在同一时刻的帧，帧数与 `count` 一致。
+pub(crate) fn encode_gif_streams<I>(count: usize, frames: I) -> crate::Result<Vec<Bytes>>
+where
+    I: IntoIterator<Item = crate::Result<Vec<Frame>>>,
+{
+    let mut buffers = vec![Vec::new(); count];
+    {
+        let mut encoders: Vec<_> = buffers
+            .iter_mut()
+            .map(image::codecs::gif::GifEncoder::new)
+            .collect();
+        for encoder in &mut encoders {
+            encoder.set_repeat(Repeat::Infinite)?;
+        }
+        for frames in frames {
+            for (encoder, frame) in encoders.iter_mut().zip(frames?) {
+                encoder.encode_frame(frame)?;
+            }
+        }
+    }
+    Ok(buffers.into_iter().map(Bytes::from).collect())
+}
+
+/// 按需逐帧解码动图
+///
+/// 输入为 GIF 或带动画的 WebP 时返回帧迭代器，否则返回 `None`。
+pub(crate) fn decode_frames(data: &[u8]) -> crate::Result<Option<Frames<'_>>> {
+    match image::guess_format(data) {
+        Ok(image::ImageFormat::Gif) => {
+            let decoder = GifDecoder::new(Cursor::new(data))?;
+            Ok(Some(decoder.into_frames()))
+        }
+        Ok(image::ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(Cursor::new(data))?;
+            if decoder.has_animation() {
+                Ok(Some(decoder.into_frames()))
+            } else {
+                Ok(None)
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+/// 循环解码动图帧
+///
+/// 解码到最后一帧后重新创建解码器从第一帧继续，内存中只保留当前帧。
+/// 输入不是动图时返回 `None`。
+pub(crate) fn cycle_frames(
+    data: &[u8],
+) -> crate::Result<Option<impl Iterator<Item = crate::Result<Frame>> + '_>> {
+    let Some(mut frames) = decode_frames(data)? else {
+        return Ok(None);
+    };
+    // 一整轮都没有解码出帧时结束，避免无限重启
+    let mut empty = true;
+    Ok(Some(std::iter::from_fn(move || {
+        loop {
+            match frames.next() {
+                Some(frame) => {
+                    empty = false;
+                    return Some(frame.map_err(Error::from));
+                }
+                None if empty => return None,
+                None => match decode_frames(data) {
+                    Ok(Some(restarted)) => {
+                        frames = restarted;
+                        empty = true;
+                    }
+                    Ok(None) => return None,
+                    Err(error) => return Some(Err(error)),
+                },
+            }
+        }
+    })))
+}
+
+/// 非动图输入时返回的错误
+pub(crate) fn animation_unsupported() -> Error {
+    ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+        ImageFormatHint::Unknown,
+        UnsupportedErrorKind::GenericFeature("animation".to_string()),
+    ))
+    .into()
+}
+
+/// 按行分块处理时每块的行数
+const ROW_CHUNK: u32 = 64;
+
+/// 按行分块并行生成图像
+///
+/// 在当前并行策略对应的线程池中执行，每块处理前检查当前任务的取消状态，处理后按行报告进度，任务被取消时返回 [`Error::Cancelled`]。
+///
+/// # 参数
+/// - `width`: 输出宽度
+/// - `height`: 输出高度
+/// - `f`: 生成一块像素，参数为该块第一行的行号和该块的 RGBA 数据
+pub(crate) fn render_rows<F>(width: u32, height: u32, f: F) -> crate::Result<RgbaImage>
+where
+    F: Fn(u32, &mut [u8]) -> crate::Result<()> + Sync,
+{
+    use rayon::prelude::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    let task = Task::current();
+    task.check()?;
+    let mut output = RgbaImage::new(width, height);
+    if width == 0 || height == 0 {
+        return Ok(output);
+    }
+    let stride = width as usize * 4;
+    let rows_done = AtomicU32::new(0);
+    install(|| {
+        output
+            .par_chunks_mut(stride * ROW_CHUNK as usize)
+            .enumerate()
+            .try_for_each(|(index, chunk)| -> crate::Result<()> {
+                task.check()?;
+                f(index as u32 * ROW_CHUNK, chunk)?;
+                let rows = (chunk.len() / stride) as u32;
+                task.report(
+                    rows_done.fetch_add(rows, Ordering::Relaxed) + rows,
+                    Some(height),
+                );
+                Ok(())
+            })
+    })?;
+    Ok(output)
+}
+
+/// 缩放 RGBA 图像
+///
+/// 启用 `simd` 特性时使用 `fast_image_resize` 作为后端，否则使用 `image::imageops`。
+pub(crate) fn resize_rgba(
+    image: &RgbaImage,
+    width: u32,
+    height: u32,
+    filter: FilterType,
+) -> crate::Result<RgbaImage> {
+    #[cfg(feature = "simd")]
+    {
+        use fast_image_resize as fr;
+
+        let algorithm = match filter {
+            FilterType::Nearest => fr::ResizeAlg::Nearest,
+            FilterType::Triangle => fr::ResizeAlg::Convolution(fr::FilterType::Bilinear),
+            FilterType::CatmullRom => fr::ResizeAlg::Convolution(fr::FilterType::CatmullRom),
+            FilterType::Gaussian => fr::ResizeAlg::Convolution(fr::FilterType::Gaussian),
+            FilterType::Lanczos3 => fr::ResizeAlg::Convolution(fr::FilterType::Lanczos3),
+        };
+        // 每块只缩放原图中对应的区域，卷积仍会用到区域外的像素，分块边界处没有接缝
+        let (source_width, source_height) = image.dimensions();
+        let scale = source_height as f64 / height.max(1) as f64;
+        render_rows(width, height, |top, chunk| {
+            let rows = (chunk.len() / (width as usize * 4)) as u32;
+            let mut resized =
+                fr::images::Image::from_slice_u8(width, rows, chunk, fr::PixelType::U8x4)
+                    .map_err(|error| Error::Other(error.to_string()))?;
+            // 浮点误差可能让最后一块略微超出原图，裁剪区域限制在原图范围内
+            let crop_top = top as f64 * scale;
+            let crop_bottom = ((top + rows) as f64 * scale).min(source_height as f64);
+            let options = fr::ResizeOptions::new().resize_alg(algorithm).crop(
+                0.0,
+                crop_top,
+                source_width as f64,
+                crop_bottom - crop_top,
+            );
+            fr::Resizer::new().resize(image, &mut resized, &options)?;
+            Ok(())
+        })
+    }
+    #[cfg(not(feature = "simd"))]
+    {
+        // image 的缩放无法分块，只在缩放前后检查取消状态
+        let task = Task::current();
+        task.check()?;
+        let resized = image::imageops::resize(image, width, height, filter);
+        task.check()?;
+        task.report(height, Some(height));
+        Ok(resized)
+    }
+}
+
+/// 以 DCT 缩放的方式解码 JPEG
+///
+/// 解码器按 1/2、1/4、1/8 的比例缩小，且保证结果在两个方向上都不小于目标尺寸。
+/// 输入不是 JPEG、无法缩小或像素格式不受支持时返回 `None`，由调用方回退到完整解码。
+pub(crate) fn decode_jpeg_scaled(data: &[u8], width: u32, height: u32) -> Option<RgbaImage> {
+    use jpeg_decoder::{Decoder, PixelFormat};
+
+    if image::guess_format(data).ok()? != image::ImageFormat::Jpeg {
+        return None;
+    }
+
+    let mut decoder = Decoder::new(Cursor::new(data));
+    decoder.read_info().ok()?;
+    let info = decoder.info()?;
+    let (full_width, full_height) = (info.width as u32, info.height as u32);
+    if full_width == 0 || full_height == 0 {
+        return None;
+    }
+
+    // 解码器只要求某一个方向满足目标尺寸，这里按原图比例补齐另一个方向，
+    // 在 u64 中计算以免目标尺寸过大时溢出
+    let requested_width =
+        (width as u64).max((height as u64 * full_width as u64).div_ceil(full_height as u64));
+    let requested_height =
+        (height as u64).max((width as u64 * full_height as u64).div_ceil(full_width as u64));
+    if requested_width * 2 > full_width as u64 || requested_height * 2 > full_height as u64 {
+        return None;
+    }
+
+    let (scaled_width, scaled_height) = decoder
+        .scale(requested_width as u16, requested_height as u16)
+        .ok()?;
+    let (scaled_width, scaled_height) = (scaled_width as u32, scaled_height as u32);
+    if scaled_width < width || scaled_height < height {
+        return None;
+    }
+
+    let pixels = decoder.decode().ok()?;
+    match info.pixel_format {
+        PixelFormat::L8 => GrayImage::from_raw(scaled_width, scaled_height, pixels)
+            .map(|image| image::DynamicImage::ImageLuma8(image).into_rgba8()),
+        PixelFormat::RGB24 => RgbImage::from_raw(scaled_width, scaled_height, pixels)
+            .map(|image| image::DynamicImage::ImageRgb8(image).into_rgba8()),
+        _ => None,
+    }
+}
+
+/// 将图像放置到新画布上
+///
+/// `x`、`y` 为图像左上角在画布中的坐标，可以为负；未被图像覆盖的部分按 `fill` 填充。
+pub(crate) fn place(
+    image: &RgbaImage,
+    width: u32,
+    height: u32,
+    x: i64,
+    y: i64,
+    fill: Fill,
+) -> RgbaImage {
+    let sample: fn(i64, u32) -> u32 = match fill {
+        Fill::Transparent | Fill::Color(_) => {
+            let background = match fill {
+                Fill::Color(color) => color,
+                _ => Rgba([0, 0, 0, 0]),
+            };
+            let mut canvas = RgbaImage::from_pixel(width, height, background);
+            image::imageops::replace(&mut canvas, image, x, y);
+            return canvas;
+        }
+        Fill::Edge => |value, size| value.clamp(0, size as i64 - 1) as u32,
+        Fill::Mirror => |value, size| {
+            let period = size as i64 * 2;
+            let value = value.rem_euclid(period);
+            (if value < size as i64 {
+                value
+            } else {
+                period - 1 - value
+            }) as u32
+        },
+    };
+
+    let (image_width, image_height) = image.dimensions();
+    if image_width == 0 || image_height == 0 {
+        return RgbaImage::new(width, height);
+    }
+    RgbaImage::from_fn(width, height, |cx, cy| {
+        let sx = sample(cx as i64 - x, image_width);
+        let sy = sample(cy as i64 - y, image_height);
+        *image.get_pixel(sx, sy)
+    })
+}
+
+/// 按投影变换图像
+///
+/// # 参数
+/// - `projection`: 从原图坐标到输出坐标的投影
+/// - `width`: 输出宽度
+/// - `height`: 输出高度
+/// - `interpolation`: 插值方式
+/// - `fill`: 原图范围之外的填充方式
+pub(crate) fn warp(
+    image: &RgbaImage,
+    projection: Projection,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+    fill: Fill,
+) -> crate::Result<RgbaImage> {
+    let interpolation = interpolation.into();
+    let inverse = projection.invert();
+    let (image_width, image_height) = image.dimensions();
+    let border = match fill {
+        Fill::Transparent => Border::Constant(Rgba([0, 0, 0, 0])),
+        Fill::Color(color) => Border::Constant(color),
+        Fill::Edge | Fill::Mirror => Border::Replicate,
+    };
+    // imageproc 没有镜像边界，镜像填充时先把采样坐标反射回原图范围内
+    let reflect = |value: f32, size: u32| {
+        let period = size as f32 * 2.0;
+        let value = (value + 0.5).rem_euclid(period);
+        (if value < size as f32 {
+            value
+        } else {
+            period - value
+        }) - 0.5
+    };
+
+    render_rows(width, height, |top, chunk| {
+        let rows = (chunk.len() / (width as usize * 4)) as u32;
+        let top = top as f32;
+        let mut output = RgbaImage::new(width, rows);
+        if let Fill::Mirror = fill {
+            warp_into_with(
+                image,
+                move |x, y| {
+                    let (x, y) = inverse * (x, y + top);
+                    (reflect(x, image_width), reflect(y, image_height))
+                },
+                interpolation,
+                border,
+                &mut output,
+            );
+        } else {
+            warp_into_with(
+                image,
+                move |x, y| inverse * (x, y + top),
+                interpolation,
+                border,
+                &mut output,
+            );
+        }
+        chunk.copy_from_slice(&output);
+        Ok(())
+    })
+}
+
+/// 以预乘透明度的方式双线性采样，坐标以像素左上角为原点
+fn sample_premultiplied(image: &RgbaImage, x: f32, y: f32) -> [f32; 4] {
+    let (width, height) = image.dimensions();
+    let x = (x - 0.5).clamp(0.0, (width - 1) as f32);
+    let y = (y - 0.5).clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let mut result = [0.0; 4];
+    for (px, py, weight) in [
+        (x0, y0, (1.0 - fx) * (1.0 - fy)),
+        (x1, y0, fx * (1.0 - fy)),
+        (x0, y1, (1.0 - fx) * fy),
+        (x1, y1, fx * fy),
+    ] {
+        let Rgba([r, g, b, a]) = *image.get_pixel(px, py);
+        let alpha = a as f32 / 255.0 * weight;
+        result[0] += r as f32 * alpha;
+        result[1] += g as f32 * alpha;
+        result[2] += b as f32 * alpha;
+        result[3] += alpha;
+    }
+    result
+}
+
+/// 点到四边形边界的距离，点在四边形内部时为正
+fn quad_distance(quad: &[(f32, f32); 4], (x, y): (f32, f32)) -> f32 {
+    let mut inside = false;
+    let mut distance = f32::INFINITY;
+    for (index, &(ax, ay)) in quad.iter().enumerate() {
+        let (bx, by) = quad[(index + 1) % 4];
+        if (ay > y) != (by > y) && x < ax + (y - ay) / (by - ay) * (bx - ax) {
+            inside = !inside;
+        }
+        let (dx, dy) = (bx - ax, by - ay);
+        let length = dx * dx + dy * dy;
+        let t = if length > 0.0 {
+            (((x - ax) * dx + (y - ay) * dy) / length).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        distance = distance.min((x - ax - t * dx).hypot(y - ay - t * dy));
+    }
+    if inside { distance } else { -distance }
+}
+
+/// 将图像透视映射到四边形上
+///
+/// 四边形之外透明，边缘按像素覆盖率抗锯齿。
+///
+/// # 参数
+/// - `inverse`: 从输出坐标到原图坐标的投影
+/// - `quad`: 输出中的四边形顶点
+/// - `width`: 输出宽度
+/// - `height`: 输出高度
+pub(crate) fn warp_quad(
+    image: &RgbaImage,
+    inverse: Projection,
+    quad: [(f32, f32); 4],
+    width: u32,
+    height: u32,
+) -> crate::Result<RgbaImage> {
+    if image.width() == 0 || image.height() == 0 {
+        return Ok(RgbaImage::new(width, height));
+    }
+    render_rows(width, height, |top, chunk| {
+        for (index, row) in chunk.chunks_mut(width as usize * 4).enumerate() {
+            let y = top as usize + index;
+            for (x, pixel) in row.as_chunks_mut::<4>().0.iter_mut().enumerate() {
+                let center = (x as f32 + 0.5, y as f32 + 0.5);
+                let coverage = (quad_distance(&quad, center) + 0.5).clamp(0.0, 1.0);
+                if coverage == 0.0 {
+                    continue;
+                }
+                let (sx, sy) = inverse * center;
+                let [r, g, b, a] = sample_premultiplied(image, sx, sy);
+                if a <= 0.0 {
+                    continue;
+                }
+                *pixel = [
+                    (r / a).round().clamp(0.0, 255.0) as u8,
+                    (g / a).round().clamp(0.0, 255.0) as u8,
+                    (b / a).round().clamp(0.0, 255.0) as u8,
+                    (a * coverage * 255.0).round().clamp(0.0, 255.0) as u8,
+                ];
+            }
+        }
+        Ok(())
+    })
+}
+
+/// 将图像按混合模式和透明度叠加到底图上，超出底图的部分会被裁掉
+///
+/// # 参数
+/// - `base`: 底图
+/// - `top`: 叠加的图像
+/// - `x`: 叠加位置的 X 坐标，可以为负数
+/// - `y`: 叠加位置的 Y 坐标，可以为负数
+/// - `opacity`: 不透明度，取值 0.0 ~ 1.0
+/// - `mode`: 混合模式
+pub(crate) fn composite(
+    base: &mut RgbaImage,
+    top: &RgbaImage,
+    x: i64,
+    y: i64,
+    opacity: f32,
+    mode: BlendMode,
+) {
+    let opacity = opacity.clamp(0.0, 1.0);
+    let left = x.max(0);
+    let upper = y.max(0);
+    let right = (x + top.width() as i64).min(base.width() as i64);
+    let lower = (y + top.height() as i64).min(base.height() as i64);
+
+    for by in upper..lower {
+        for bx in left..right {
+            let source = *top.get_pixel((bx - x) as u32, (by - y) as u32);
+            composite_pixel(
+                base.get_pixel_mut(bx as u32, by as u32),
+                source,
+                opacity,
+                mode,
+            );
+        }
+    }
+}
+
+/// 渲染单行文字，文字之外的部分透明
+///
+/// # 参数
+/// - `text`: 文字内容
+/// - `font`: 字体文件数据
+/// - `size`: 字号，单位为像素
+/// - `color`: 文字颜色
+pub(crate) fn render_text(
+    text: &str,
+    font: &[u8],
+    size: f32,
+    color: Rgba<u8>,
+) -> crate::Result<RgbaImage> {
+    use ab_glyph::FontRef;
+    use imageproc::drawing::{draw_text_mut, text_size};
+    let font = FontRef::try_from_slice(font)?;
+    let (width, height) = text_size(size, &font, text);
+    let [r, g, b, _] = color.0;
+    let mut canvas = RgbaImage::from_pixel(width.max(1), height.max(1), Rgba([r, g, b, 0]));
+    draw_text_mut(&mut canvas, color, 0, 0, size, &font, text);
+    Ok(canvas)
+}
+
+/// 计算圆角矩形在各像素上的覆盖率，取值 0.0 ~ 1.0
+///
+/// 以像素中心到边界的距离近似覆盖率，边缘抗锯齿。
+///
+/// # 参数
+/// - `width`: 矩形宽度
+/// - `height`: 矩形高度
+/// - `radius`: 圆角半径，超过短边一半时按短边一半计算
+pub(crate) fn rounded_coverage(width: u32, height: u32, radius: f32) -> impl Fn(u32, u32) -> f32 {
+    let (half_width, half_height) = (width as f32 / 2.0, height as f32 / 2.0);
+    let radius = radius.clamp(0.0, half_width.min(half_height));
+    move |x, y| {
+        // 折叠到右下象限，只需处理一个圆角
+        let dx = (x as f32 + 0.5 - half_width).abs() - (half_width - radius);
+        let dy = (y as f32 + 0.5 - half_height).abs() - (half_height - radius);
+        let distance = if dx > 0.0 && dy > 0.0 {
+            dx.hypot(dy) - radius
+        } else {
+            dx.max(dy) - radius
+        };
+        (0.5 - distance).clamp(0.0, 1.0)
+    }
+}
+
+/// 按透明度蒙版生成模糊的纯色图层，用于阴影和发光
+///
+/// # 参数
+/// - `mask`: 透明度蒙版
+/// - `blur_radius`: 模糊半径，为 0 时不模糊
+/// - `color`: 图层颜色
+/// - `opacity`: 图层的不透明度，取值 0.0 ~ 1.0
+pub(crate) fn shadow_layer(
+    mask: GrayImage,
+    blur_radius: f32,
+    color: Rgba<u8>,
+    opacity: f32,
+) -> RgbaImage {
+    use imageproc::filter::gaussian_blur_f32;
+    let mask = if blur_radius > 0.0 {
+        gaussian_blur_f32(&mask, blur_radius / 2.0)
+    } else {
+        mask
+    };
+    let [r, g, b, a] = color.0;
+    let scale = a as f32 / 255.0 * opacity.clamp(0.0, 1.0);
+    RgbaImage::from_fn(mask.width(), mask.height(), |x, y| {
+        Rgba([
+            r,
+            g,
+            b,
+            (mask.get_pixel(x, y)[0] as f32 * scale).round() as u8,
+        ])
+    })
+}
+
+/// 模糊半径对应的扩展距离，覆盖约 3 倍标准差
+pub(crate) fn blur_extent(blur_radius: f32) -> u32 {
+    (blur_radius.max(0.0) * 1.5).ceil() as u32
+}
+
+/// 查找与边框颜色不同的内容区域
+///
+/// 以左上角像素为参考色，参考色透明时按透明度判断，返回 `(left, top, right, bottom)`，右下边界不包含在内。
+/// 整张图像都与参考色相同时返回 `None`。
+pub(crate) fn content_bounds(image: &RgbaImage, tolerance: u8) -> Option<(u32, u32, u32, u32)> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return None;
+    }
+    let reference = *image.get_pixel(0, 0);
+    let is_background = |pixel: &image::Rgba<u8>| {
+        if reference[3] == 0 {
+            pixel[3] <= tolerance
+        } else {
+            pixel
+                .0
+                .iter()
+                .zip(reference.0)
+                .all(|(&a, b)| a.abs_diff(b) <= tolerance)
+        }
+    };
+
+    let (mut left, mut top, mut right, mut bottom) = (width, height, 0, 0);
+    for (x, y, pixel) in image.enumerate_pixels() {
+        if !is_background(pixel) {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x + 1);
+            bottom = bottom.max(y + 1);
+        }
+    }
+    (right > left && bottom > top).then_some((left, top, right, bottom))
+}
+
+/// 判断每一行是否为纯色
+///
+/// 以行首像素为参考色，整行各通道与之差值都不超过 `tolerance` 时视为纯色行。
+pub(crate) fn uniform_rows(image: &RgbaImage, tolerance: u8) -> Vec<bool> {
+    use rayon::prelude::*;
+    let width = image.width() as usize;
+    if width == 0 {
+        return vec![true; image.height() as usize];
+    }
+    image
+        .as_raw()
+        .par_chunks(width * 4)
+        .map(|row| {
+            let (pixels, _) = row.as_chunks::<4>();
+            let reference = pixels[0];
+            pixels.iter().all(|pixel| {
+                pixel
+                    .iter()
+                    .zip(reference)
+                    .all(|(&a, b)| a.abs_diff(b) <= tolerance)
+            })
+        })
+        .collect()
+}
+
+/// 计算灰度图指定区域的香农熵
+fn entropy(image: &GrayImage, x: u32, y: u32, width: u32, height: u32) -> f64 {
+    let mut histogram = [0u32; 256];
+    for py in y..y + height {
+        for px in x..x + width {
+            let Luma([value]) = *image.get_pixel(px, py);
+            histogram[value as usize] += 1;
+        }
+    }
+    let total = (width as u64 * height as u64) as f64;
+    if total == 0.0 {
+        return 0.0;
+    }
+    histogram
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// 查找信息量最大的裁剪窗口
+///
+/// 每次比较两侧的条带，裁掉熵较低的一侧，直到窗口缩小到目标尺寸，返回窗口左上角坐标。
+pub(crate) fn entropy_window(
+    image: &GrayImage,
+    width: u32,
+    height: u32,
+) -> crate::Result<(u32, u32)> {
+    const SLICE: u32 = 10;
+    let task = Task::current();
+    let (mut left, mut top) = (0u32, 0u32);
+    let (mut right, mut bottom) = image.dimensions();
+    let width = width.min(right);
+    let height = height.min(bottom);
+
+    while right - left > width {
+        task.check()?;
+        let slice = (right - left - width).min(SLICE);
+        let rows = bottom - top;
+        if entropy(image, left, top, slice, rows) < entropy(image, right - slice, top, slice, rows)
+        {
+            left += slice;
+        } else {
+            right -= slice;
+        }
+    }
+    while bottom - top > height {
+        task.check()?;
+        let slice = (bottom - top - height).min(SLICE);
+        let columns = right - left;
+        if entropy(image, left, top, columns, slice)
+            < entropy(image, left, bottom - slice, columns, slice)
+        {
+            top += slice;
+        } else {
+            bottom -= slice;
+        }
+    }
+    Ok((left, top))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CancelToken;
+
+    fn pattern(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([
+                (x * 255 / width) as u8,
+                (y * 255 / height) as u8,
+                ((x * 7 + y * 13) % 256) as u8,
+                255 - (x % 64) as u8,
+            ])
+        })
+    }
+
+    #[test]
+    fn resize_matches_imageops() {
+        let source = pattern(257, 193);
+        let filters = [
+            FilterType::Nearest,
+            FilterType::Triangle,
+            FilterType::CatmullRom,
+            FilterType::Gaussian,
+            FilterType::Lanczos3,
+        ];
+        for (width, height) in [(100, 77), (123, 401), (600, 50)] {
+            for filter in filters {
+                let expected = image::imageops::resize(&source, width, height, filter);
+                let actual = resize_rgba(&source, width, height, filter).unwrap();
+                assert_eq!(actual.dimensions(), (width, height));
+                let diffs: Vec<u8> = actual
+                    .as_raw()
+                    .iter()
+                    .zip(expected.as_raw())
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .collect();
+                let max = diffs.iter().copied().max().unwrap();
+                let mean = diffs.iter().map(|&d| d as f64).sum::<f64>() / diffs.len() as f64;
+                assert!(
+                    max <= 32 && mean <= 1.0,
+                    "{width}x{height} {filter:?}: max {max}, mean {mean}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn render_rows_cancelled() {
+        let token = CancelToken::new();
+        token.cancel();
+        let task = Task::new().with_cancel_token(token);
+        let result = task.run(|| resize_rgba(&pattern(64, 64), 32, 32, FilterType::Triangle));
+        assert!(matches!(result, Err(Error::Cancelled)));
+
+        // 处理中途取消时不再处理剩余的行
+        let token = CancelToken::new();
+        let cancel = token.clone();
+        let task = Task::new()
+            .with_cancel_token(token)
+            .with_progress(move |_| cancel.cancel());
+        let result = task.run(|| render_rows(8, ROW_CHUNK * 64, |_, _| Ok(())));
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn content_bounds_of_border() {
+        let mut image = RgbaImage::new(10, 10);
+        for (x, y) in [(4, 5), (6, 6)] {
+            image.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        }
+        assert_eq!(content_bounds(&image, 0), Some((4, 5, 7, 7)));
+
+        // 透明边框只比较透明度
+        image.put_pixel(0, 9, Rgba([255, 255, 255, 3]));
+        assert_eq!(content_bounds(&image, 3), Some((4, 5, 7, 7)));
+        assert_eq!(content_bounds(&image, 2), Some((0, 5, 7, 10)));
+
+        let mut image = RgbaImage::from_pixel(8, 8, Rgba([200, 200, 200, 255]));
+        assert_eq!(content_bounds(&image, 0), None);
+        image.put_pixel(3, 2, Rgba([204, 198, 200, 255]));
+        assert_eq!(content_bounds(&image, 4), None);
+        assert_eq!(content_bounds(&image, 3), Some((3, 2, 4, 3)));
+    }
+
+    #[test]
+    fn uniform_rows_with_tolerance() {
+        let mut image = RgbaImage::from_pixel(4, 3, Rgba([100, 100, 100, 255]));
+        image.put_pixel(2, 1, Rgba([108, 100, 100, 255]));
+        image.put_pixel(3, 2, Rgba([109, 100, 100, 255]));
+        assert_eq!(uniform_rows(&image, 8), vec![true, true, false]);
+        assert_eq!(uniform_rows(&image, 0), vec![true, false, false]);
+    }
+
+    #[test]
+    fn canvas_limit() {
+        assert!(canvas_fits(16384, 8192));
+        assert!(!canvas_fits(16384, 8193));
+        assert!(!canvas_fits(u64::MAX, 2));
+    }
+}
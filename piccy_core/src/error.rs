@@ -20,6 +20,38 @@ pub enum Error {
         #[source]
         source: base64::DecodeError,
     },
+    #[error("Thread pool build error")]
+    ThreadPool {
+        #[from]
+        #[source]
+        source: rayon::ThreadPoolBuildError,
+    },
+    #[cfg(feature = "simd")]
+    #[error("Resize error")]
+    Resize {
+        #[from]
+        #[source]
+        source: fast_image_resize::ResizeError,
+    },
+    #[error("Invalid font")]
+    Font {
+        #[from]
+        #[source]
+        source: ab_glyph::InvalidFont,
+    },
+    #[error(
+        "Region {width}x{height} at ({x}, {y}) is out of image bounds {image_width}x{image_height}"
+    )]
+    OutOfBounds {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+        image_width: u32,
+        image_height: u32,
+    },
+    #[error("Operation cancelled")]
+    Cancelled,
     #[error("Other error: {0}")]
     Other(String),
 }
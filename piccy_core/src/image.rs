@@ -1,21 +1,31 @@
+use crate::blend::tint_pixel;
 use crate::error::Error;
+use crate::parallel::install;
+use crate::task::Task;
 use rayon::iter::ParallelIterator;
 
-use crate::common::encode_gif;
-use crate::{AnimationInfo, FlipMode, ImageFormat, ImageInfo, MergeMode, Result};
+use crate::common::{
+    MAX_CANVAS_BYTES, animation_unsupported, blur_extent, canvas_fits, composite, content_bounds,
+    cycle_frames, decode_frames, decode_jpeg_scaled, encode_gif_stream, encode_gif_streams,
+    entropy_window, place, render_text, resize_rgba, rounded_coverage, shadow_layer, uniform_rows,
+    warp, warp_quad,
+};
+use crate::{
+    AnimationInfo, BlendMode, Dimensions, Fill, FlipMode, GlowMode, Gravity, ImageFormat,
+    ImageInfo, Interpolation, MaskChannel, MergeMode, MergeOptions, OverlayOptions, ResampleFilter,
+    ResizeFit, ResizeOptions, Result, TransformOptions, Watermark, WatermarkOptions,
+};
 use base64::{Engine, engine::general_purpose::STANDARD};
 use bytes::Bytes;
 use image::{
     AnimationDecoder, DynamicImage,
     DynamicImage::ImageRgba8,
-    Frame, GenericImageView, ImageReader, Rgb, RgbaImage,
+    Frame, GenericImageView, GrayImage, ImageDecoder, ImageReader, Luma, Rgb, Rgba, RgbaImage,
     codecs::{gif::GifDecoder, webp::WebPDecoder},
-    error::{
-        ImageError, ImageFormatHint, ParameterError, ParameterErrorKind, UnsupportedError,
-        UnsupportedErrorKind,
-    },
+    error::{ImageError, ImageFormatHint, UnsupportedError, UnsupportedErrorKind},
     imageops::FilterType,
 };
+use imageproc::geometric_transformations::Projection;
 use rayon::iter::IntoParallelIterator;
 use std::time::Duration;
 use std::{io::Cursor, path::Path};
@@ -54,11 +64,8 @@ impl Image {
         match reader.format() {
             Some(ImageFormat::Gif) => {
                 let decoder = GifDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
-                let animation_info = AnimationInfo::from(&frames);
-                let (width, height) = frames.first()
-                    .map(|f| (f.buffer().width(), f.buffer().height()))
-                    .unwrap_or((0, 0));
+                let (width, height) = decoder.dimensions();
+                let animation_info = AnimationInfo::try_from(decoder.into_frames())?;
                 Ok(ImageInfo {
                     size: self.0.len(),
                     dimensions: crate::Dimensions { width, height },
@@ -67,11 +74,8 @@ impl Image {
             }
             Some(ImageFormat::WebP) => {
                 let decoder = WebPDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
-                let animation_info = AnimationInfo::from(&frames);
-                let (width, height) = frames.first()
-                    .map(|f| (f.buffer().width(), f.buffer().height()))
-                    .unwrap_or((0, 0));
+                let (width, height) = decoder.dimensions();
+                let animation_info = AnimationInfo::try_from(decoder.into_frames())?;
                 Ok(ImageInfo {
                     size: self.0.len(),
                     dimensions: crate::Dimensions { width, height },
@@ -79,13 +83,10 @@ impl Image {
                 })
             }
             _ => {
-                let image = reader.decode()?;
+                let (width, height) = reader.into_dimensions()?;
                 Ok(ImageInfo {
                     size: self.0.len(),
-                    dimensions: crate::Dimensions {
-                        width: image.width(),
-                        height: image.height(),
-                    },
+                    dimensions: crate::Dimensions { width, height },
                     animation: None,
                 })
             }
@@ -93,34 +94,22 @@ impl Image {
     }
 
     /// 编码为字节数据
+    ///
+    /// 动图输入会逐帧解码并写入 GIF 编码器，不会同时持有全部帧。
     pub fn to_bytes(&self, format: ImageFormat) -> Result<Bytes> {
-        use image::ImageFormat as ImgFmt;
-
-        let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let input_format = reader.format();
-
-        let gif_out = |frames: Vec<image::Frame>| -> Result<Bytes> { encode_gif(frames) };
-
-        let image = if matches!(input_format, Some(ImgFmt::Gif) | Some(ImgFmt::WebP)) {
+        if let Some(frames) = decode_frames(&self.0)? {
             if format != ImageFormat::Gif {
                 return Err(Error::Other(
-                    "multi-frame input cannot be encoded as single-frame format; use Gif".to_string(),
+                    "multi-frame input cannot be encoded as single-frame format; use Gif"
+                        .to_string(),
                 ));
             }
-            let frames: Vec<image::Frame> = match input_format {
-                Some(ImgFmt::Gif) => {
-                    GifDecoder::new(Cursor::new(&self.0))?.into_frames().collect_frames()?
-                }
-                Some(ImgFmt::WebP) => {
-                    WebPDecoder::new(Cursor::new(&self.0))?.into_frames().collect_frames()?
-                }
-                _ => unreachable!(),
-            };
-            return gif_out(frames);
-        } else {
-            reader.decode()?
-        };
+            let frames = frames.map(|frame| frame.map_err(Error::from));
+            return encode_gif_stream(Task::current().track(frames, None));
+        }
+
+        let cursor = Cursor::new(&self.0);
+        let image = ImageReader::new(cursor).with_guessed_format()?.decode()?;
 
         let mut buffer = Vec::new();
         let mut cursor = Cursor::new(&mut buffer);
@@ -167,20 +156,208 @@ impl Image {
     /// - `y`: 裁剪的左上角 Y 坐标
     /// - `width`: 裁剪的宽度
     /// - `height`: 裁剪的高度
+    ///
+    /// 裁剪区域超出图像范围时返回 [`Error::OutOfBounds`]。
     pub fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        self.crop_with(|image_width, image_height| {
+            if x as u64 + width as u64 > image_width as u64
+                || y as u64 + height as u64 > image_height as u64
+            {
+                return Err(Error::OutOfBounds {
+                    x,
+                    y,
+                    width,
+                    height,
+                    image_width,
+                    image_height,
+                });
+            }
+            Ok((x, y, width, height))
+        })
+    }
+
+    /// 裁剪图像，超出图像范围的部分会被截去
+    ///
+    /// # 参数
+    /// - `x`: 裁剪的左上角 X 坐标
+    /// - `y`: 裁剪的左上角 Y 坐标
+    /// - `width`: 裁剪的宽度
+    /// - `height`: 裁剪的高度
+    pub fn crop_clamped(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        self.crop_with(|image_width, image_height| {
+            let clamped_x = x.min(image_width);
+            let clamped_y = y.min(image_height);
+            let clamped_width = width.min(image_width - clamped_x);
+            let clamped_height = height.min(image_height - clamped_y);
+            if clamped_width == 0 || clamped_height == 0 {
+                return Err(Error::OutOfBounds {
+                    x,
+                    y,
+                    width,
+                    height,
+                    image_width,
+                    image_height,
+                });
+            }
+            Ok((clamped_x, clamped_y, clamped_width, clamped_height))
+        })
+    }
+
+    /// 按相对坐标裁剪图像
+    ///
+    /// # 参数
+    /// - `x`: 左上角 X 坐标占图像宽度的比例，取值 0.0 ~ 1.0
+    /// - `y`: 左上角 Y 坐标占图像高度的比例，取值 0.0 ~ 1.0
+    /// - `width`: 裁剪宽度占图像宽度的比例，取值 0.0 ~ 1.0
+    /// - `height`: 裁剪高度占图像高度的比例，取值 0.0 ~ 1.0
+    ///
+    /// 超出图像范围的部分会被截去。
+    pub fn crop_relative(&self, x: f32, y: f32, width: f32, height: f32) -> Result<Self> {
+        self.crop_with(|image_width, image_height| {
+            let scale =
+                |value: f32, size: u32| (value.clamp(0.0, 1.0) * size as f32).round() as u32;
+            let left = scale(x, image_width);
+            let top = scale(y, image_height);
+            let right = scale(x + width, image_width).max(left);
+            let bottom = scale(y + height, image_height).max(top);
+            if right == left || bottom == top {
+                return Err(Error::OutOfBounds {
+                    x: left,
+                    y: top,
+                    width: right - left,
+                    height: bottom - top,
+                    image_width,
+                    image_height,
+                });
+            }
+            Ok((left, top, right - left, bottom - top))
+        })
+    }
+
+    /// 按宽高比裁剪出最大的区域
+    ///
+    /// # 参数
+    /// - `width`: 宽高比中的宽
+    /// - `height`: 宽高比中的高
+    /// - `gravity`: 裁剪区域的锚点，默认居中
+    pub fn crop_aspect(&self, width: u32, height: u32, gravity: Option<Gravity>) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(Error::Other("aspect ratio must be positive".to_string()));
+        }
+        let gravity = gravity.unwrap_or_default();
+        self.crop_with(|image_width, image_height| {
+            let (crop_width, crop_height) =
+                if image_width as u64 * height as u64 > image_height as u64 * width as u64 {
+                    let crop_width = image_height as u64 * width as u64 / height as u64;
+                    ((crop_width as u32).max(1), image_height)
+                } else {
+                    let crop_height = image_width as u64 * height as u64 / width as u64;
+                    (image_width, (crop_height as u32).max(1))
+                };
+            let (x, y) = gravity.position((image_width, image_height), (crop_width, crop_height));
+            Ok((x as u32, y as u32, crop_width, crop_height))
+        })
+    }
+
+    /// 智能裁剪
+    ///
+    /// 先将图像等比缩放到恰好覆盖目标尺寸，再保留信息量（灰度熵）最大的区域，适合生成头像缩略图。
+    ///
+    /// # 参数
+    /// - `width`: 输出宽度
+    /// - `height`: 输出高度
+    /// - `filter`: 重采样滤波器，默认 Lanczos3
+    pub fn smart_crop(
+        &self,
+        width: u32,
+        height: u32,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
+        use image::ImageFormat;
+        let cursor = Cursor::new(&self.0);
+        let source = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .into_dimensions()?;
+        let (_, (scaled_width, scaled_height)) =
+            ResizeFit::Cover.dimensions(source, Some(width), Some(height));
+
+        let image = match decode_jpeg_scaled(&self.0, scaled_width, scaled_height) {
+            Some(image) => image,
+            None => {
+                let cursor = Cursor::new(&self.0);
+                let reader = ImageReader::new(cursor).with_guessed_format()?;
+                reader.decode()?.into_rgba8()
+            }
+        };
+        let filter = filter.unwrap_or_default().into();
+        let scaled = resize_rgba(&image, scaled_width, scaled_height, filter)?;
+
+        let gray = ImageRgba8(scaled.clone()).into_luma8();
+        let (x, y) = entropy_window(&gray, width, height)?;
+        let cropped = scaled
+            .view(x, y, width.min(scaled_width), height.min(scaled_height))
+            .to_image();
+
+        let mut buffer = Vec::new();
+        ImageRgba8(cropped).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        Ok(Self(buffer.into()))
+    }
+
+    /// 裁掉纯色边框或透明边距
+    ///
+    /// 以左上角像素为边框颜色，找出与之不同的内容区域并裁剪。动图取所有帧内容区域的并集。
+    /// 整张图像都是边框颜色时返回原图。
+    ///
+    /// # 参数
+    /// - `tolerance`: 各通道允许的颜色差值，为 0 时要求完全一致
+    pub fn trim(&self, tolerance: u8) -> Result<Self> {
+        let bounds = match decode_frames(&self.0)? {
+            Some(frames) => {
+                let mut bounds: Option<(u32, u32, u32, u32)> = None;
+                for frame in
+                    Task::current().track(frames.map(|frame| frame.map_err(Error::from)), None)
+                {
+                    let Some(frame_bounds) = content_bounds(frame?.buffer(), tolerance) else {
+                        continue;
+                    };
+                    bounds = Some(match bounds {
+                        Some((left, top, right, bottom)) => (
+                            left.min(frame_bounds.0),
+                            top.min(frame_bounds.1),
+                            right.max(frame_bounds.2),
+                            bottom.max(frame_bounds.3),
+                        ),
+                        None => frame_bounds,
+                    });
+                }
+                bounds
+            }
+            None => {
+                let cursor = Cursor::new(&self.0);
+                let reader = ImageReader::new(cursor).with_guessed_format()?;
+                let image = reader.decode()?.into_rgba8();
+                Task::current().check()?;
+                content_bounds(&image, tolerance)
+            }
+        };
+
+        let Some((left, top, right, bottom)) = bounds else {
+            return Ok(self.clone());
+        };
+        self.map_frames(|image| Ok(image.view(left, top, right - left, bottom - top).to_image()))
+    }
+
+    /// 按图像尺寸计算出的区域裁剪图像
+    fn crop_with<F>(&self, region: F) -> Result<Self>
+    where
+        F: FnOnce(u32, u32) -> Result<(u32, u32, u32, u32)>,
+    {
         use image::ImageFormat;
         let cursor = Cursor::new(&self.0);
         let reader = ImageReader::new(cursor).with_guessed_format()?;
         let image = reader.decode()?;
 
-        let (image_width, image_height) = (image.width(), image.height());
-        if x + width > image_width || y + height > image_height {
-            return Err(ImageError::Parameter(ParameterError::from_kind(
-                ParameterErrorKind::DimensionMismatch,
-            ))
-            .into());
-        }
-
+        let (x, y, width, height) = region(image.width(), image.height())?;
         let cropped = image.view(x, y, width, height).to_image();
         let mut buffer = Vec::new();
         ImageRgba8(cropped).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
@@ -190,91 +367,374 @@ impl Image {
     /// 缩放图像
     ///
     /// # 参数
-    /// - `width`: 缩放后的宽度
-    /// - `height`: 缩放后的高度
-    pub fn resize(&self, width: u32, height: u32) -> Result<Self> {
+    /// - `width`: 缩放后的宽度，不指定时按宽高比计算
+    /// - `height`: 缩放后的高度，不指定时按宽高比计算
+    /// - `options`: 缩放选项，默认拉伸到目标尺寸
+    ///
+    /// 缩小 JPEG 时会先以 DCT 缩放的方式解码到接近目标的尺寸，再进行重采样。
+    pub fn resize(
+        &self,
+        width: Option<u32>,
+        height: Option<u32>,
+        options: Option<ResizeOptions>,
+    ) -> Result<Self> {
         use image::ImageFormat;
+        use image::imageops;
+        let options = options.unwrap_or_default();
+
         let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let image = reader.decode()?;
+        let source = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .into_dimensions()?;
+        let ((canvas_width, canvas_height), (scaled_width, scaled_height)) =
+            options.fit.dimensions(source, width, height);
+
+        let image = match decode_jpeg_scaled(&self.0, scaled_width, scaled_height) {
+            Some(image) => image,
+            None => {
+                let cursor = Cursor::new(&self.0);
+                let reader = ImageReader::new(cursor).with_guessed_format()?;
+                reader.decode()?.into_rgba8()
+            }
+        };
+
+        let resized = resize_rgba(&image, scaled_width, scaled_height, options.filter.into())?;
+
+        let output = if (scaled_width, scaled_height) == (canvas_width, canvas_height) {
+            resized
+        } else {
+            let (x, y) = options
+                .gravity
+                .position((canvas_width, canvas_height), (scaled_width, scaled_height));
+            let mut canvas = RgbaImage::from_pixel(canvas_width, canvas_height, options.background);
+            if options.background[3] == 0 {
+                imageops::replace(&mut canvas, &resized, x, y);
+            } else {
+                imageops::overlay(&mut canvas, &resized, x, y);
+            }
+            canvas
+        };
 
-        let resized = image.resize_exact(width, height, FilterType::Lanczos3);
         let mut buffer = Vec::new();
-        ImageRgba8(resized.to_rgba8()).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        ImageRgba8(output).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
         Ok(Self(buffer.into()))
     }
 
+    /// 扩展图像边距
+    ///
+    /// # 参数
+    /// - `top`: 上边距
+    /// - `right`: 右边距
+    /// - `bottom`: 下边距
+    /// - `left`: 左边距
+    /// - `fill`: 边距的填充方式，默认透明
+    pub fn pad(
+        &self,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        fill: Option<Fill>,
+    ) -> Result<Self> {
+        let fill = fill.unwrap_or_default();
+        self.map_frames(|image| {
+            let (width, height) = image.dimensions();
+            let canvas_width = width as u64 + left as u64 + right as u64;
+            let canvas_height = height as u64 + top as u64 + bottom as u64;
+            if !canvas_fits(canvas_width, canvas_height) {
+                return Err(Error::Other("padded canvas is too large".to_string()));
+            }
+            Ok(place(
+                &image,
+                canvas_width as u32,
+                canvas_height as u32,
+                left as i64,
+                top as i64,
+                fill,
+            ))
+        })
+    }
+
+    /// 将图像放到更大的画布上
+    ///
+    /// 画布尺寸不会小于原图。
+    ///
+    /// # 参数
+    /// - `width`: 画布宽度
+    /// - `height`: 画布高度
+    /// - `anchor`: 图像在画布中的位置，默认居中
+    /// - `fill`: 空白部分的填充方式，默认透明
+    pub fn extend_to(
+        &self,
+        width: u32,
+        height: u32,
+        anchor: Option<Gravity>,
+        fill: Option<Fill>,
+    ) -> Result<Self> {
+        let anchor = anchor.unwrap_or_default();
+        let fill = fill.unwrap_or_default();
+        self.map_frames(|image| {
+            let canvas = (width.max(image.width()), height.max(image.height()));
+            if !canvas_fits(canvas.0 as u64, canvas.1 as u64) {
+                return Err(Error::Other("extended canvas is too large".to_string()));
+            }
+            let (x, y) = anchor.position(canvas, image.dimensions());
+            Ok(place(&image, canvas.0, canvas.1, x, y, fill))
+        })
+    }
+
     /// 旋转图像
     ///
     /// # 参数
-    /// - `angle`: 旋转的角度（度）
-    pub fn rotate(&self, angle: f32) -> Result<Self> {
+    /// - `angle`: 顺时针旋转的角度（度）
+    /// - `options`: 变换选项，默认保持原画布尺寸、双线性插值、透明填充
+    ///
+    /// 角度为 90 的整数倍时直接重排像素，不进行插值。
+    /// 其他角度通过投影变换采样原图，[`Fill::Mirror`] 会把画布外的采样坐标反射回原图内。
+    pub fn rotate(&self, angle: f32, options: Option<TransformOptions>) -> Result<Self> {
         use image::ImageFormat;
+        use image::imageops;
+        let options = options.unwrap_or_default();
         let cursor = Cursor::new(&self.0);
         let reader = ImageReader::new(cursor).with_guessed_format()?;
         let image = reader.decode()?.to_rgba8();
 
-        let rotated = imageproc::geometric_transformations::rotate_about_center(
-            &image,
-            angle.to_radians(),
-            imageproc::geometric_transformations::Interpolation::Bilinear,
-            imageproc::geometric_transformations::Border::Constant(image::Rgba([0, 0, 0, 0])),
-        );
+        let quarter_turns = angle / 90.0;
+        let rotated = if (quarter_turns - quarter_turns.round()).abs() < 1e-4 {
+            let rotated = match (quarter_turns.round() as i64).rem_euclid(4) {
+                1 => imageops::rotate90(&image),
+                2 => imageops::rotate180(&image),
+                3 => imageops::rotate270(&image),
+                _ => image.clone(),
+            };
+            if options.expand || rotated.dimensions() == image.dimensions() {
+                rotated
+            } else {
+                let (width, height) = image.dimensions();
+                let (x, y) = Gravity::Center.position((width, height), rotated.dimensions());
+                place(&rotated, width, height, x, y, options.fill)
+            }
+        } else {
+            let (width, height) = image.dimensions();
+            let theta = angle.to_radians();
+            let (sin, cos) = theta.sin_cos();
+            let (output_width, output_height) = if options.expand {
+                (
+                    (height as f32 * sin.abs() + width as f32 * cos.abs()).ceil() as u32,
+                    (height as f32 * cos.abs() + width as f32 * sin.abs()).ceil() as u32,
+                )
+            } else {
+                (width, height)
+            };
+            let projection =
+                Projection::translate(output_width as f32 / 2.0, output_height as f32 / 2.0)
+                    * Projection::rotate(theta)
+                    * Projection::translate(-(width as f32) / 2.0, -(height as f32) / 2.0);
+            warp(
+                &image,
+                projection,
+                output_width,
+                output_height,
+                options.interpolation,
+                options.fill,
+            )?
+        };
 
         let mut buffer = Vec::new();
         ImageRgba8(rotated).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
         Ok(Self(buffer.into()))
     }
 
+    /// 仿射变换
+    ///
+    /// # 参数
+    /// - `matrix`: 行优先的 2x3 仿射矩阵 `[a, b, c, d, e, f]`，即 `x' = ax + by + c`、`y' = dx + ey + f`
+    /// - `options`: 变换选项，默认保持原画布尺寸、双线性插值、透明填充
+    pub fn affine(&self, matrix: [f32; 6], options: Option<TransformOptions>) -> Result<Self> {
+        let [a, b, c, d, e, f] = matrix;
+        self.transform(
+            |_, _| Projection::from_matrix([a, b, c, d, e, f, 0.0, 0.0, 1.0]),
+            options.unwrap_or_default(),
+        )
+    }
+
+    /// 水平错切
+    ///
+    /// # 参数
+    /// - `angle`: 错切角度（度），为正时图像下半部分向右倾斜
+    /// - `options`: 变换选项，默认保持原画布尺寸、双线性插值、透明填充
+    pub fn shear_x(&self, angle: f32, options: Option<TransformOptions>) -> Result<Self> {
+        self.shear(angle.to_radians().tan(), 0.0, options)
+    }
+
+    /// 垂直错切
+    ///
+    /// # 参数
+    /// - `angle`: 错切角度（度），为正时图像右半部分向下倾斜
+    /// - `options`: 变换选项，默认保持原画布尺寸、双线性插值、透明填充
+    pub fn shear_y(&self, angle: f32, options: Option<TransformOptions>) -> Result<Self> {
+        self.shear(0.0, angle.to_radians().tan(), options)
+    }
+
+    /// 以图像中心为原点错切
+    fn shear(&self, x: f32, y: f32, options: Option<TransformOptions>) -> Result<Self> {
+        self.transform(
+            |width, height| {
+                let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+                let shear = Projection::from_matrix([1.0, x, 0.0, y, 1.0, 0.0, 0.0, 0.0, 1.0])?;
+                Some(Projection::translate(cx, cy) * shear * Projection::translate(-cx, -cy))
+            },
+            options.unwrap_or_default(),
+        )
+    }
+
+    /// 透视变换
+    ///
+    /// 把原图中的四边形映射到输出画布中的四边形，输出四边形之外透明，边缘抗锯齿。
+    /// 顶点按顺时针或逆时针依次给出，坐标以像素左上角为原点。
+    ///
+    /// # 参数
+    /// - `src_quad`: 原图中的四边形顶点，例如整张图为 `[(0, 0), (w, 0), (w, h), (0, h)]`
+    /// - `dst_quad`: 输出画布中对应的四边形顶点
+    /// - `out_size`: 输出画布尺寸
+    pub fn warp_perspective(
+        &self,
+        src_quad: [(f32, f32); 4],
+        dst_quad: [(f32, f32); 4],
+        out_size: Dimensions,
+    ) -> Result<Self> {
+        let Dimensions { width, height } = out_size;
+        if width == 0 || height == 0 {
+            return Err(Error::Other("output size must be positive".to_string()));
+        }
+        if width as u64 * height as u64 * 4 > MAX_CANVAS_BYTES {
+            return Err(Error::Other("output image is too large".to_string()));
+        }
+        let inverse = Projection::from_control_points(dst_quad, src_quad)
+            .ok_or_else(|| Error::Other("perspective control points are degenerate".to_string()))?;
+        self.map_frames(|image| warp_quad(&image, inverse, dst_quad, width, height))
+    }
+
+    /// 对每一帧应用投影变换
+    ///
+    /// 扩展画布时把变换结果平移到画布内。
+    fn transform<F>(&self, projection: F, options: TransformOptions) -> Result<Self>
+    where
+        F: Fn(u32, u32) -> Option<Projection>,
+    {
+        self.map_frames(|image| {
+            let (width, height) = image.dimensions();
+            let projection = projection(width, height)
+                .ok_or_else(|| Error::Other("transform matrix is not invertible".to_string()))?;
+            let (projection, output_width, output_height) = if options.expand {
+                let corners = [
+                    (0.0, 0.0),
+                    (width as f32, 0.0),
+                    (0.0, height as f32),
+                    (width as f32, height as f32),
+                ]
+                .map(|corner| projection * corner);
+                let (min_x, min_y, max_x, max_y) = corners.iter().fold(
+                    (
+                        f32::INFINITY,
+                        f32::INFINITY,
+                        f32::NEG_INFINITY,
+                        f32::NEG_INFINITY,
+                    ),
+                    |(min_x, min_y, max_x, max_y), &(x, y)| {
+                        (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                    },
+                );
+                let (output_width, output_height) = (max_x - min_x, max_y - min_y);
+                let bytes = output_width * output_height * 4.0;
+                if !bytes.is_finite() || bytes > MAX_CANVAS_BYTES as f32 {
+                    return Err(Error::Other("transformed image is too large".to_string()));
+                }
+                (
+                    Projection::translate(-min_x, -min_y) * projection,
+                    (output_width.ceil() as u32).max(1),
+                    (output_height.ceil() as u32).max(1),
+                )
+            } else {
+                (projection, width, height)
+            };
+            warp(
+                &image,
+                projection,
+                output_width,
+                output_height,
+                options.interpolation,
+                options.fill,
+            )
+        })
+    }
+
     /// 翻转图像
     ///
     /// # 参数
     /// - `mode`: 翻转模式
     pub fn flip(&self, mode: Option<FlipMode>) -> Result<Self> {
+        use image::imageops;
+        let mode = mode.unwrap_or_default();
+        self.map_frames(|image| {
+            Ok(match mode {
+                FlipMode::Horizontal => imageops::flip_horizontal(&image),
+                FlipMode::Vertical => imageops::flip_vertical(&image),
+                FlipMode::Both => imageops::rotate180(&image),
+                FlipMode::Transpose => imageops::flip_horizontal(&imageops::rotate90(&image)),
+                FlipMode::Transverse => imageops::flip_vertical(&imageops::rotate90(&image)),
+            })
+        })
+    }
+
+    /// 逐帧处理图像
+    ///
+    /// 动图会逐帧解码、处理并写入 GIF 编码器，处理完的帧会立即释放；静态图像处理后编码为 PNG。
+    ///
+    /// # 参数
+    /// - `f`: 作用于每一帧的处理函数
+    pub fn map_frames<F>(&self, mut f: F) -> Result<Self>
+    where
+        F: FnMut(RgbaImage) -> Result<RgbaImage>,
+    {
         use image::ImageFormat;
+        let task = Task::current();
+        if let Some(frames) = decode_frames(&self.0)? {
+            // 按帧报告进度，帧内的操作只检查取消状态
+            let silent = task.silent();
+            let frames = frames.map(|frame| {
+                let frame = frame?;
+                let (left, top, delay) = (frame.left(), frame.top(), frame.delay());
+                let buffer = silent.run(|| f(frame.into_buffer()))?;
+                Ok(Frame::from_parts(buffer, left, top, delay))
+            });
+            return encode_gif_stream(task.track(frames, None)).map(Self);
+        }
+
+        task.check()?;
         let cursor = Cursor::new(&self.0);
         let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let image = reader.decode()?;
-
-        let mode = mode.unwrap_or_default();
-        let flipped = match mode {
-            FlipMode::Horizontal => image.fliph(),
-            FlipMode::Vertical => image.flipv(),
-        };
+        let image = f(reader.decode()?.into_rgba8())?;
 
         let mut buffer = Vec::new();
-        ImageRgba8(flipped.to_rgba8()).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
         Ok(Self(buffer.into()))
     }
 
     /// 灰度化图像
     pub fn grayscale(&self) -> Result<Self> {
-        use image::ImageFormat;
-        let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let image = reader.decode()?;
-
-        let gray = image.grayscale();
-        let mut buffer = Vec::new();
-        ImageRgba8(gray.to_rgba8()).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
-        Ok(Self(buffer.into()))
+        self.map_frames(|image| Ok(ImageRgba8(image).grayscale().into_rgba8()))
     }
 
     /// 反色图像
     pub fn invert(&self) -> Result<Self> {
-        use image::ImageFormat;
-        let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let mut image = reader.decode()?.into_rgba8();
-
-        image.pixels_mut().for_each(|pixel| {
-            let [r, g, b, a] = pixel.0;
-            pixel.0 = [255 - r, 255 - g, 255 - b, a];
-        });
-
-        let mut buffer = Vec::new();
-        ImageRgba8(image).write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
-        Ok(Self(buffer.into()))
+        self.map_frames(|mut image| {
+            image.pixels_mut().for_each(|pixel| {
+                let [r, g, b, a] = pixel.0;
+                pixel.0 = [255 - r, 255 - g, 255 - b, a];
+            });
+            Ok(image)
+        })
     }
 
     /// 颜色蒙版
@@ -282,37 +742,275 @@ impl Image {
     /// # 参数
     /// - `color`: RGB 颜色值
     pub fn color_mask(&self, color: Rgb<u8>) -> Result<Self> {
-        use image::ImageFormat;
         let Rgb([r, g, b]) = color;
 
-        let cursor = Cursor::new(&self.0);
+        self.map_frames(|mut image| {
+            image.pixels_mut().for_each(|pixel| {
+                let [red, green, blue, alpha] = pixel.0;
+                let src_alpha = alpha as f32 / 255.0;
+                pixel.0 = [
+                    ((r as f32) * src_alpha * 0.5 + (red as f32) * (1.0 - src_alpha * 0.5)).round()
+                        as u8,
+                    ((g as f32) * src_alpha * 0.5 + (green as f32) * (1.0 - src_alpha * 0.5))
+                        .round() as u8,
+                    ((b as f32) * src_alpha * 0.5 + (blue as f32) * (1.0 - src_alpha * 0.5)).round()
+                        as u8,
+                    alpha,
+                ];
+            });
+            Ok(image)
+        })
+    }
+
+    /// 圆形蒙版
+    ///
+    /// 保留以图像中心为圆心、短边为直径的圆形区域，其余部分透明，边缘抗锯齿。
+    pub fn mask_circle(&self) -> Result<Self> {
+        self.map_frames(|mut image| {
+            let (width, height) = image.dimensions();
+            let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+            let radius = cx.min(cy);
+            for (x, y, pixel) in image.enumerate_pixels_mut() {
+                let distance = (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy) - radius;
+                let coverage = (0.5 - distance).clamp(0.0, 1.0);
+                pixel[3] = (pixel[3] as f32 * coverage).round() as u8;
+            }
+            Ok(image)
+        })
+    }
+
+    /// 圆角蒙版
+    ///
+    /// # 参数
+    /// - `radius`: 圆角半径，超过短边一半时按短边一半计算
+    pub fn mask_rounded(&self, radius: f32) -> Result<Self> {
+        self.map_frames(|mut image| {
+            let coverage = rounded_coverage(image.width(), image.height(), radius);
+            for (x, y, pixel) in image.enumerate_pixels_mut() {
+                pixel[3] = (pixel[3] as f32 * coverage(x, y)).round() as u8;
+            }
+            Ok(image)
+        })
+    }
+
+    /// 应用蒙版
+    ///
+    /// 按蒙版指定通道的值缩放图像的透明度，蒙版尺寸与图像不同时会先拉伸到图像尺寸。
+    ///
+    /// # 参数
+    /// - `mask`: 蒙版图片，动图只使用第一帧
+    /// - `channel`: 作为透明度的蒙版通道，默认透明度通道
+    /// - `filter`: 拉伸蒙版时使用的重采样滤波器，默认双线性
+    pub fn apply_mask(
+        &self,
+        mask: &Image,
+        channel: Option<MaskChannel>,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
+        let channel = channel.unwrap_or_default();
+        let filter: FilterType = filter.unwrap_or(ResampleFilter::Bilinear).into();
+        let cursor = Cursor::new(&mask.0);
         let reader = ImageReader::new(cursor).with_guessed_format()?;
-        let mut image = reader.decode()?.into_rgba8();
-
-        image.pixels_mut().for_each(|pixel| {
-            let [red, green, blue, alpha] = pixel.0;
-            let src_alpha = alpha as f32 / 255.0;
-            pixel.0 = [
-                ((r as f32) * src_alpha * 0.5 + (red as f32) * (1.0 - src_alpha * 0.5)).round()
-                    as u8,
-                ((g as f32) * src_alpha * 0.5 + (green as f32) * (1.0 - src_alpha * 0.5)).round()
-                    as u8,
-                ((b as f32) * src_alpha * 0.5 + (blue as f32) * (1.0 - src_alpha * 0.5)).round()
-                    as u8,
-                alpha,
-            ];
-        });
+        let mask = reader.decode()?.into_rgba8();
+        let mut resized: Option<RgbaImage> = None;
+
+        self.map_frames(|mut image| {
+            let mask = if mask.dimensions() == image.dimensions() {
+                &mask
+            } else {
+                if resized.as_ref().map(RgbaImage::dimensions) != Some(image.dimensions()) {
+                    resized = Some(resize_rgba(&mask, image.width(), image.height(), filter)?);
+                }
+                resized.as_ref().unwrap()
+            };
+            for (pixel, value) in image.pixels_mut().zip(mask.pixels()) {
+                let [r, g, b, a] = value.0;
+                let value = match channel {
+                    MaskChannel::Alpha => a as f32,
+                    MaskChannel::Luminance => {
+                        0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32
+                    }
+                    MaskChannel::Red => r as f32,
+                    MaskChannel::Green => g as f32,
+                    MaskChannel::Blue => b as f32,
+                };
+                pixel[3] = (pixel[3] as f32 * value / 255.0).round() as u8;
+            }
+            Ok(image)
+        })
+    }
 
-        let mut buffer = Vec::new();
-        image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
-        Ok(Self(buffer.into()))
+    /// 描边
+    ///
+    /// 沿不透明内容的轮廓向外描边，画布四周各扩展描边宽度，适合制作贴纸。
+    ///
+    /// # 参数
+    /// - `width`: 描边宽度，为 0 时原样返回
+    /// - `color`: 描边颜色
+    pub fn outline(&self, width: u32, color: Rgba<u8>) -> Result<Self> {
+        use imageproc::distance_transform::euclidean_squared_distance_transform;
+        if width == 0 {
+            return Ok(self.clone());
+        }
+        let [r, g, b, a] = color.0;
+        self.map_frames(|image| {
+            let canvas_width = image.width() as u64 + width as u64 * 2;
+            let canvas_height = image.height() as u64 + width as u64 * 2;
+            if !canvas_fits(canvas_width, canvas_height) {
+                return Err(Error::Other("outline width is too large".to_string()));
+            }
+            let (canvas_width, canvas_height) = (canvas_width as u32, canvas_height as u32);
+            let content = place(
+                &image,
+                canvas_width,
+                canvas_height,
+                width as i64,
+                width as i64,
+                Fill::Transparent,
+            );
+
+            // 以半透明以上的像素作为轮廓，按到轮廓的距离计算描边覆盖率
+            let foreground = GrayImage::from_fn(canvas_width, canvas_height, |x, y| {
+                Luma([(content.get_pixel(x, y)[3] >= 128) as u8])
+            });
+            let distances = euclidean_squared_distance_transform(&foreground);
+            let mut output = RgbaImage::from_fn(canvas_width, canvas_height, |x, y| {
+                let distance = distances.get_pixel(x, y)[0].sqrt() as f32;
+                let coverage = (width as f32 + 0.5 - distance).clamp(0.0, 1.0);
+                Rgba([r, g, b, (a as f32 * coverage).round() as u8])
+            });
+            composite(&mut output, &content, 0, 0, 1.0, BlendMode::Normal);
+            Ok(output)
+        })
+    }
+
+    /// 投影
+    ///
+    /// 由透明度通道生成阴影并放在图像下方，画布会扩展到足以容纳完整的阴影。
+    ///
+    /// # 参数
+    /// - `offset_x`: 阴影的水平偏移量
+    /// - `offset_y`: 阴影的垂直偏移量
+    /// - `blur_radius`: 模糊半径，为 0 时阴影边缘清晰
+    /// - `color`: 阴影颜色
+    /// - `opacity`: 阴影的不透明度，取值 0.0 ~ 1.0
+    pub fn drop_shadow(
+        &self,
+        offset_x: i32,
+        offset_y: i32,
+        blur_radius: f32,
+        color: Rgba<u8>,
+        opacity: f32,
+    ) -> Result<Self> {
+        let extent = blur_extent(blur_radius) as i64;
+        let (offset_x, offset_y) = (offset_x as i64, offset_y as i64);
+        let left = (extent - offset_x).max(0);
+        let right = (extent + offset_x).max(0);
+        let top = (extent - offset_y).max(0);
+        let bottom = (extent + offset_y).max(0);
+
+        self.map_frames(|image| {
+            let canvas_width = (image.width() as i64 + left + right) as u64;
+            let canvas_height = (image.height() as i64 + top + bottom) as u64;
+            if !canvas_fits(canvas_width, canvas_height) {
+                return Err(Error::Other("shadow canvas is too large".to_string()));
+            }
+            let (canvas_width, canvas_height) = (canvas_width as u32, canvas_height as u32);
+            let mask = GrayImage::from_fn(canvas_width, canvas_height, |x, y| {
+                let sx = x as i64 - left - offset_x;
+                let sy = y as i64 - top - offset_y;
+                if (0..image.width() as i64).contains(&sx)
+                    && (0..image.height() as i64).contains(&sy)
+                {
+                    Luma([image.get_pixel(sx as u32, sy as u32)[3]])
+                } else {
+                    Luma([0])
+                }
+            });
+            let mut output = shadow_layer(mask, blur_radius, color, opacity);
+            composite(&mut output, &image, left, top, 1.0, BlendMode::Normal);
+            Ok(output)
+        })
+    }
+
+    /// 发光
+    ///
+    /// 外发光会扩展画布以容纳完整的光晕，内发光沿内容边缘向内发光，画布尺寸不变。
+    ///
+    /// # 参数
+    /// - `radius`: 发光半径
+    /// - `color`: 发光颜色
+    /// - `opacity`: 发光的不透明度，取值 0.0 ~ 1.0
+    /// - `mode`: 发光模式，默认外发光
+    pub fn glow(
+        &self,
+        radius: f32,
+        color: Rgba<u8>,
+        opacity: f32,
+        mode: Option<GlowMode>,
+    ) -> Result<Self> {
+        match mode.unwrap_or_default() {
+            GlowMode::Outer => self.drop_shadow(0, 0, radius, color, opacity),
+            GlowMode::Inner => {
+                let extent = blur_extent(radius);
+                self.map_frames(|mut image| {
+                    // 图像之外视为透明，边缘同样向内发光
+                    let (width, height) = image.dimensions();
+                    let mask_width = width as u64 + extent as u64 * 2;
+                    let mask_height = height as u64 + extent as u64 * 2;
+                    if !canvas_fits(mask_width, mask_height) {
+                        return Err(Error::Other("glow canvas is too large".to_string()));
+                    }
+                    let (mask_width, mask_height) = (mask_width as u32, mask_height as u32);
+                    let mask = GrayImage::from_fn(mask_width, mask_height, |x, y| {
+                        let (sx, sy) = (x as i64 - extent as i64, y as i64 - extent as i64);
+                        if (0..width as i64).contains(&sx) && (0..height as i64).contains(&sy) {
+                            Luma([255 - image.get_pixel(sx as u32, sy as u32)[3]])
+                        } else {
+                            Luma([255])
+                        }
+                    });
+                    let layer = shadow_layer(mask, radius, color, opacity);
+                    for (x, y, pixel) in image.enumerate_pixels_mut() {
+                        let mut glow = *layer.get_pixel(x + extent, y + extent);
+                        glow[3] = (glow[3] as f32 * pixel[3] as f32 / 255.0).round() as u8;
+                        tint_pixel(pixel, glow, 1.0, BlendMode::Normal);
+                    }
+                    Ok(image)
+                })
+            }
+        }
+    }
+
+    /// 按混合模式将纯色混合到图像上，图像的透明度保持不变
+    ///
+    /// # 参数
+    /// - `color`: 混合的颜色
+    /// - `mode`: 混合模式，默认正常
+    /// - `opacity`: 颜色的不透明度，取值 0.0 ~ 1.0
+    pub fn blend_color(
+        &self,
+        color: Rgba<u8>,
+        mode: Option<BlendMode>,
+        opacity: f32,
+    ) -> Result<Self> {
+        let mode = mode.unwrap_or_default();
+        let opacity = opacity.clamp(0.0, 1.0);
+        self.map_frames(|mut image| {
+            image
+                .pixels_mut()
+                .for_each(|pixel| tint_pixel(pixel, color, opacity, mode));
+            Ok(image)
+        })
     }
 
     /// 幻影坦克
     ///
     /// # 参数
     /// - `hidden`: 需要隐藏的图片
-    pub fn mirage(&self, hidden: &Self) -> Result<Self> {
+    /// - `filter`: 缩放到相同尺寸时使用的重采样滤波器，默认 Lanczos3
+    pub fn mirage(&self, hidden: &Self, filter: Option<ResampleFilter>) -> Result<Self> {
+        let filter: FilterType = filter.unwrap_or_default().into();
         let cursor1 = Cursor::new(&self.0);
         let img1 = ImageReader::new(cursor1).with_guessed_format()?.decode()?;
 
@@ -322,8 +1020,9 @@ impl Image {
         let w = img1.width().min(img2.width());
         let h = img1.height().min(img2.height());
 
-        let img1_rgba = img1.resize_exact(w, h, FilterType::Lanczos3).to_rgba8();
-        let img2_rgba = img2.resize_exact(w, h, FilterType::Lanczos3).to_rgba8();
+        let silent = Task::current().silent();
+        let img1_rgba = silent.run(|| resize_rgba(&img1.into_rgba8(), w, h, filter))?;
+        let img2_rgba = silent.run(|| resize_rgba(&img2.into_rgba8(), w, h, filter))?;
 
 
         let calc_avg_luminance = |img: &RgbaImage| -> f32 {
@@ -352,7 +1051,8 @@ impl Image {
             0.5
         };
 
-        self.mirage_internal(img1_rgba, img2_rgba, white_light, black_light)
+        Task::current().check()?;
+        install(|| self.mirage_internal(img1_rgba, img2_rgba, white_light, black_light))
     }
 
     /// 幻影坦克内部实现
@@ -384,16 +1084,27 @@ impl Image {
 
         use rayon::prelude::*;
         use std::sync::Arc;
+        use std::sync::atomic::{AtomicU32, Ordering};
 
         let img1 = Arc::new(img1);
         let img2 = Arc::new(img2);
 
+        let task = Task::current();
+        let rows_done = AtomicU32::new(0);
+
         let pixels: Vec<_> = (0..h)
             .into_par_iter()
             .flat_map(|y| {
                 let img1 = Arc::clone(&img1);
                 let img2 = Arc::clone(&img2);
-                (0..w).into_par_iter().map(move |x| {
+                // 已取消时跳过剩余行
+                let row_width = if task.is_cancelled() {
+                    0
+                } else {
+                    task.report(rows_done.fetch_add(1, Ordering::Relaxed) + 1, Some(h));
+                    w
+                };
+                (0..row_width).into_par_iter().map(move |x| {
                     let wpixel = img1.get_pixel(x, y);
                     let bpixel = img2.get_pixel(x, y);
 
@@ -438,6 +1149,7 @@ impl Image {
                 })
             })
             .collect();
+        task.check()?;
 
         let mut out_img = RgbaImage::new(w, h);
         for (x, y, pixel) in pixels {
@@ -451,67 +1163,25 @@ impl Image {
 
     /// 分离动图帧
     ///
+    /// 逐帧解码并编码为 PNG，输入不是动图或只有一帧时返回错误。
     pub fn split(&self) -> Result<Vec<Self>> {
         use image::ImageFormat;
-        let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
-
-        match reader.format() {
-            Some(ImageFormat::Gif) => {
-                let decoder = GifDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
-
-                if frames.len() <= 1 {
-                    return Err(
-                        ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                            ImageFormatHint::Unknown,
-                            UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                        ))
-                        .into(),
-                    );
-                }
-                frames
-                    .into_iter()
-                    .map(|frame| {
-                        let mut buffer = Vec::new();
-                        let img = ImageRgba8(frame.into_buffer());
-                        img.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
-                        Ok(Image(buffer.into()))
-                    })
-                    .collect()
-            }
-            Some(ImageFormat::WebP) => {
-                let decoder = WebPDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
-
-                if frames.len() <= 1 {
-                    return Err(
-                        ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                            ImageFormatHint::Unknown,
-                            UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                        ))
-                        .into(),
-                    );
-                }
-
-                frames
-                    .into_iter()
-                    .map(|frame| {
-                        let mut buffer = Vec::new();
-                        let img = ImageRgba8(frame.into_buffer());
-                        img.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
-                        Ok(Self(buffer.into()))
-                    })
-                    .collect()
-            }
-            _ => Err(
-                ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                    ImageFormatHint::Unknown,
-                    UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                ))
-                .into(),
-            ),
+        let Some(frames) = decode_frames(&self.0)? else {
+            return Err(animation_unsupported());
+        };
+        let frames = frames.map(|frame| {
+            let mut buffer = Vec::new();
+            ImageRgba8(frame?.into_buffer())
+                .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+            Ok(Self(buffer.into()))
+        });
+        let images = Task::current()
+            .track(frames, None)
+            .collect::<Result<Vec<_>>>()?;
+        if images.len() <= 1 {
+            return Err(animation_unsupported());
         }
+        Ok(images)
     }
 
     /// 反转动图帧顺序
@@ -535,8 +1205,9 @@ impl Image {
                     );
                 }
 
-                let reversed_frames: Vec<Frame> = frames.into_iter().rev().collect();
-                encode_gif(reversed_frames).map(Self)
+                let total = frames.len() as u32;
+                let reversed_frames = frames.into_iter().rev().map(Ok);
+                encode_gif_stream(Task::current().track(reversed_frames, Some(total))).map(Self)
             }
             Some(ImageFormat::WebP) => {
                 let decoder = WebPDecoder::new(Cursor::new(&self.0))?;
@@ -552,8 +1223,9 @@ impl Image {
                     );
                 }
 
-                let reversed_frames: Vec<Frame> = frames.into_iter().rev().collect();
-                encode_gif(reversed_frames).map(Self)
+                let total = frames.len() as u32;
+                let reversed_frames = frames.into_iter().rev().map(Ok);
+                encode_gif_stream(Task::current().track(reversed_frames, Some(total))).map(Self)
             }
             _ => Err(
                 ImageError::Unsupported(UnsupportedError::from_format_and_kind(
@@ -570,71 +1242,164 @@ impl Image {
     /// # 参数
     /// - `duration`: 帧间隔时间
     pub fn change_duration(&self, duration: Duration) -> Result<Self> {
-        use image::ImageFormat;
-        let cursor = Cursor::new(&self.0);
-        let reader = ImageReader::new(cursor).with_guessed_format()?;
+        let Some(frames) = decode_frames(&self.0)? else {
+            return Err(animation_unsupported());
+        };
 
-        match reader.format() {
-            Some(ImageFormat::Gif) => {
-                let decoder = GifDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
+        let delay = image::Delay::from_saturating_duration(duration);
+        let mut frame_count = 0usize;
+        let frames = frames.map(|frame| {
+            let frame = frame?;
+            frame_count += 1;
+            let (left, top) = (frame.left(), frame.top());
+            Ok(Frame::from_parts(frame.into_buffer(), left, top, delay))
+        });
+        let bytes = encode_gif_stream(Task::current().track(frames, None))?;
 
-                if frames.len() <= 1 {
-                    return Err(
-                        ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                            ImageFormatHint::Unknown,
-                            UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                        ))
-                        .into(),
-                    );
-                }
+        if frame_count <= 1 {
+            return Err(animation_unsupported());
+        }
+        Ok(Self(bytes))
+    }
 
-                let delay = image::Delay::from_saturating_duration(duration);
-                let frames: Vec<Frame> = frames
-                    .into_iter()
-                    .map(|frame| {
-                        let left = frame.left();
-                        let top = frame.top();
-                        let buffer = frame.into_buffer();
-                        Frame::from_parts(buffer, left, top, delay)
-                    })
-                    .collect();
-                encode_gif(frames).map(Self)
-            }
-            Some(ImageFormat::WebP) => {
-                let decoder = WebPDecoder::new(Cursor::new(&self.0))?;
-                let frames = decoder.into_frames().collect_frames()?;
+    /// 切分宫格图
+    ///
+    /// 先将图像居中放到透明画布上，使每一块都为正方形（行列数相同时画布为正方形），
+    /// 再按从左到右、从上到下的顺序切分。动图的每一块都是保留帧间隔的动图。
+    ///
+    /// # 参数
+    /// - `rows`: 行数
+    /// - `columns`: 列数
+    pub fn slice_grid(&self, rows: u32, columns: u32) -> Result<Vec<Self>> {
+        use image::ImageFormat;
+        if rows == 0 || columns == 0 {
+            return Err(Error::Other(
+                "rows and columns must be positive".to_string(),
+            ));
+        }
+        let count = rows
+            .checked_mul(columns)
+            .ok_or_else(|| Error::Other("too many grid cells".to_string()))?;
 
-                if frames.len() <= 1 {
-                    return Err(
-                        ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                            ImageFormatHint::Unknown,
-                            UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                        ))
-                        .into(),
-                    );
-                }
+        let cursor = Cursor::new(&self.0);
+        let (width, height) = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .into_dimensions()?;
+        let tile = width.div_ceil(columns).max(height.div_ceil(rows)).max(1);
+        let canvas_width = tile as u64 * columns as u64;
+        let canvas_height = tile as u64 * rows as u64;
+        if !canvas_fits(canvas_width, canvas_height) {
+            return Err(Error::Other("grid canvas is too large".to_string()));
+        }
+        let (canvas_width, canvas_height) = (canvas_width as u32, canvas_height as u32);
+        let (x, y) = Gravity::Center.position((canvas_width, canvas_height), (width, height));
+        let slice = |image: &RgbaImage| -> Vec<RgbaImage> {
+            let canvas = place(image, canvas_width, canvas_height, x, y, Fill::Transparent);
+            (0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (row, column)))
+                .map(|(row, column)| {
+                    canvas
+                        .view(column * tile, row * tile, tile, tile)
+                        .to_image()
+                })
+                .collect()
+        };
 
-                let delay = image::Delay::from_saturating_duration(duration);
-                let frames: Vec<Frame> = frames
+        if let Some(frames) = decode_frames(&self.0)? {
+            let frames = frames.map(|frame| {
+                let frame = frame?;
+                let delay = frame.delay();
+                Ok(slice(frame.buffer())
                     .into_iter()
-                    .map(|frame| {
-                        let left = frame.left();
-                        let top = frame.top();
-                        let buffer = frame.into_buffer();
-                        Frame::from_parts(buffer, left, top, delay)
-                    })
-                    .collect();
-                encode_gif(frames).map(Self)
+                    .map(|tile| Frame::from_parts(tile, 0, 0, delay))
+                    .collect())
+            });
+            let tiles = encode_gif_streams(count as usize, Task::current().track(frames, None))?;
+            return Ok(tiles.into_iter().map(Self).collect());
+        }
+
+        let cursor = Cursor::new(&self.0);
+        let image = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .decode()?
+            .into_rgba8();
+        install(|| {
+            slice(&image)
+                .into_par_iter()
+                .map(|tile| {
+                    let mut buffer = Vec::new();
+                    tile.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+                    Ok(Self(buffer.into()))
+                })
+                .collect()
+        })
+    }
+
+    /// 将长图切分为多页
+    ///
+    /// 每页高度不超过 `max_height`，切分位置优先选在纯色背景行上（例如聊天气泡之间的空隙），
+    /// 在每页的后半段找不到纯色行时直接按最大高度切分。动图只处理第一帧。
+    ///
+    /// # 参数
+    /// - `max_height`: 每页的最大高度
+    /// - `overlap`: 相邻两页重叠的高度，必须小于 `max_height`
+    pub fn split_pages(&self, max_height: u32, overlap: u32) -> Result<Vec<Self>> {
+        use image::ImageFormat;
+        if max_height == 0 {
+            return Err(Error::Other("max height must be positive".to_string()));
+        }
+        if overlap >= max_height {
+            return Err(Error::Other(
+                "overlap must be less than max height".to_string(),
+            ));
+        }
+
+        let cursor = Cursor::new(&self.0);
+        let image = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .decode()?
+            .into_rgba8();
+        let (width, height) = image.dimensions();
+        let uniform = install(|| uniform_rows(&image, 8));
+
+        let mut pages = Vec::new();
+        let mut start = 0u32;
+        loop {
+            let limit = start.saturating_add(max_height);
+            if limit >= height {
+                pages.push((start, height));
+                break;
             }
-            _ => Err(
-                ImageError::Unsupported(UnsupportedError::from_format_and_kind(
-                    ImageFormatHint::Unknown,
-                    UnsupportedErrorKind::GenericFeature("animation".to_string()),
-                ))
-                .into(),
-            ),
+            // 只在页面后半段查找切分位置，避免产生过短的页面
+            let lower = start + (max_height / 2).max(overlap + 1);
+            let cut = (lower..=limit)
+                .rev()
+                .find(|&row| uniform[row as usize])
+                .map(|bottom| {
+                    // 切在纯色区域的中间，让相邻两页都保留一部分留白
+                    let top = (lower..bottom)
+                        .rev()
+                        .take_while(|&row| uniform[row as usize])
+                        .last()
+                        .unwrap_or(bottom);
+                    (top + bottom).div_ceil(2)
+                })
+                .unwrap_or(limit);
+            pages.push((start, cut));
+            start = cut - overlap;
         }
+
+        install(|| {
+            pages
+                .into_par_iter()
+                .map(|(top, bottom)| {
+                    let page = image.view(0, top, width, bottom - top).to_image();
+                    let mut buffer = Vec::new();
+                    page.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+                    Ok(Self(buffer.into()))
+                })
+                .collect()
+        })
     }
 
     /// 拼接图片
@@ -642,9 +1407,17 @@ impl Image {
     /// # 参数
     /// - `images`: 需要拼接的其他图片
     /// - `mode`: 拼接模式
-    pub fn merge(&self, images: Vec<&Image>, mode: Option<MergeMode>) -> Result<Self> {
+    /// - `options`: 拼接选项，默认无间距、透明背景、居中对齐并缩放到统一尺寸
+    pub fn merge(
+        &self,
+        images: Vec<&Image>,
+        mode: Option<MergeMode>,
+        options: Option<MergeOptions>,
+    ) -> Result<Self> {
         use image::ImageFormat;
         use image::imageops;
+        let options = options.unwrap_or_default();
+        let filter: FilterType = options.filter.into();
         let mut all_images: Vec<&Image> = Vec::with_capacity(1 + images.len());
         all_images.push(self);
         all_images.extend(images);
@@ -664,57 +1437,144 @@ impl Image {
             return Err(Error::Other("No valid image data".to_string()));
         }
         let mode = mode.unwrap_or_default();
+        let spacing = options.spacing;
+
+        let max_width = decoded_images
+            .iter()
+            .map(|img| img.width())
+            .max()
+            .unwrap_or(0);
+        let max_height = decoded_images
+            .iter()
+            .map(|img| img.height())
+            .max()
+            .unwrap_or(0);
+        let min_height = decoded_images
+            .iter()
+            .map(|img| img.height())
+            .min()
+            .unwrap_or(0);
 
-        let merged_image = match mode {
+        // 每张图片缩放后的尺寸
+        let sizes: Vec<(u32, u32)> = decoded_images
+            .iter()
+            .map(|img| {
+                let (width, height) = img.dimensions();
+                if !options.scale {
+                    return (width, height);
+                }
+                match mode {
+                    MergeMode::Horizontal => {
+                        let scale = min_height as f32 / height as f32;
+                        ((width as f32 * scale) as u32, min_height)
+                    }
+                    MergeMode::Vertical => {
+                        let scale = max_width as f32 / width as f32;
+                        (max_width, ((height as f32 * scale) as u32).max(1))
+                    }
+                    MergeMode::Grid { .. } => {
+                        let (_, scaled) = ResizeFit::Contain.dimensions(
+                            (width, height),
+                            Some(decoded_images[0].width()),
+                            Some(decoded_images[0].height()),
+                        );
+                        scaled
+                    }
+                }
+            })
+            .collect();
+
+        // 画布尺寸与每张图片的位置，先在 u64 中计算，避免间距过大时溢出
+        let count = sizes.len() as u64;
+        let spacing = spacing as u64;
+        let gaps = (count - 1) * spacing;
+        let (canvas, positions): ((u64, u64), Vec<(i64, i64)>) = match mode {
             MergeMode::Horizontal => {
-                let min_height = decoded_images
-                    .iter()
-                    .map(|img| img.height())
-                    .min()
-                    .unwrap_or(0);
-                let total_width: u32 = decoded_images
+                let height = sizes.iter().map(|&(_, h)| h).max().unwrap_or(0);
+                let width = sizes.iter().map(|&(w, _)| w as u64).sum::<u64>() + gaps;
+                let mut x = 0;
+                let positions = sizes
                     .iter()
-                    .map(|img| {
-                        let scale = min_height as f32 / img.height() as f32;
-                        (img.width() as f32 * scale) as u32
+                    .map(|&(w, h)| {
+                        let position = (x as i64, options.align.offset(height, h));
+                        x += w as u64 + spacing;
+                        position
                     })
-                    .sum();
-                let mut merged_image = ImageRgba8(RgbaImage::new(total_width, min_height));
-                let mut current_x: u32 = 0;
-                for image in &decoded_images {
-                    let scale = min_height as f32 / image.height() as f32;
-                    let scaled_width = (image.width() as f32 * scale) as u32;
-                    let resized_image =
-                        image.resize_exact(scaled_width, min_height, FilterType::Triangle);
-                    imageops::overlay(&mut merged_image, &resized_image, current_x as i64, 0);
-                    current_x += scaled_width;
-                }
-                merged_image
+                    .collect();
+                ((width, height as u64), positions)
             }
             MergeMode::Vertical => {
-                let max_width = decoded_images
+                let width = sizes.iter().map(|&(w, _)| w).max().unwrap_or(0);
+                let height = sizes.iter().map(|&(_, h)| h as u64).sum::<u64>() + gaps;
+                let mut y = 0;
+                let positions = sizes
                     .iter()
-                    .map(|img| img.width())
-                    .max()
-                    .unwrap_or(0);
-                let total_height = decoded_images.iter().map(|img| img.height()).sum();
-                let mut merged_image = ImageRgba8(RgbaImage::new(max_width, total_height));
-                let mut current_y = 0;
-
-                for image in &decoded_images {
-                    let resized_image =
-                        image.resize_exact(max_width, image.height(), FilterType::Triangle);
-                    imageops::overlay(&mut merged_image, &resized_image, 0, current_y as i64);
-                    current_y += resized_image.height();
-                }
-                merged_image
+                    .map(|&(w, h)| {
+                        let position = (options.align.offset(width, w), y as i64);
+                        y += h as u64 + spacing;
+                        position
+                    })
+                    .collect();
+                ((width as u64, height), positions)
+            }
+            MergeMode::Grid { columns } => {
+                let columns = columns
+                    .map(u64::from)
+                    .unwrap_or_else(|| (count as f64).sqrt().ceil() as u64)
+                    .clamp(1, count);
+                let rows = count.div_ceil(columns);
+                let (cell_width, cell_height) = if options.scale {
+                    decoded_images[0].dimensions()
+                } else {
+                    (max_width, max_height)
+                };
+                let positions = sizes
+                    .iter()
+                    .zip(0u64..)
+                    .map(|(&(w, h), index)| {
+                        let cell_x = index % columns * (cell_width as u64 + spacing);
+                        let cell_y = index / columns * (cell_height as u64 + spacing);
+                        (
+                            cell_x as i64 + options.align.offset(cell_width, w),
+                            cell_y as i64 + options.align.offset(cell_height, h),
+                        )
+                    })
+                    .collect();
+                let width = cell_width as u64 * columns + (columns - 1) * spacing;
+                let height = cell_height as u64 * rows + (rows - 1) * spacing;
+                ((width, height), positions)
             }
         };
+        let fits = canvas
+            .0
+            .checked_mul(canvas.1)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .is_some_and(|bytes| bytes <= MAX_CANVAS_BYTES);
+        let (Ok(canvas_width), Ok(canvas_height), true) =
+            (u32::try_from(canvas.0), u32::try_from(canvas.1), fits)
+        else {
+            return Err(Error::Other("merged image is too large".to_string()));
+        };
+
+        let mut merged_image =
+            RgbaImage::from_pixel(canvas_width, canvas_height, options.background);
+        let task = Task::current();
+        let placements = decoded_images.iter().zip(&sizes).zip(&positions).map(Ok);
+        for placement in task.track(placements, Some(sizes.len() as u32)) {
+            let ((image, &(width, height)), &(x, y)) = placement?;
+            let image = image.to_rgba8();
+            if image.dimensions() == (width, height) {
+                imageops::overlay(&mut merged_image, &image, x, y);
+            } else {
+                let resized_image = task
+                    .silent()
+                    .run(|| resize_rgba(&image, width, height, filter))?;
+                imageops::overlay(&mut merged_image, &resized_image, x, y);
+            }
+        }
 
         let mut buffer = Vec::new();
-        merged_image
-            .into_rgba8()
-            .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
+        merged_image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?;
         Ok(Self(buffer.into()))
     }
 
@@ -723,7 +1583,14 @@ impl Image {
     /// # 参数
     /// - `images`: 其他图片列表
     /// - `duration`: 帧间隔时间
-    pub fn merge_gif(&self, images: Vec<&Image>, duration: Option<Duration>) -> Result<Self> {
+    /// - `filter`: 缩放到首张图片尺寸时使用的重采样滤波器，默认 Lanczos3
+    pub fn merge_gif(
+        &self,
+        images: Vec<&Image>,
+        duration: Option<Duration>,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
+        let filter: FilterType = filter.unwrap_or_default().into();
         let mut all_images: Vec<&Image> = Vec::with_capacity(1 + images.len());
         all_images.push(self);
         all_images.extend(images);
@@ -738,21 +1605,324 @@ impl Image {
         let height = info.dimensions.height;
         let frame_duration = duration.unwrap_or(Duration::from_millis(20));
 
-        let frames: Result<Vec<Frame>> = all_images
-            .into_par_iter()
-            .map(|image| {
-                let cursor = Cursor::new(&image.0);
-                let img = ImageReader::new(cursor).with_guessed_format()?.decode()?;
-                let resized_image = img.resize_exact(width, height, FilterType::Lanczos3);
+        let task = Task::current();
+        let total = all_images.len() as u32;
+        let images_done = std::sync::atomic::AtomicU32::new(0);
+        let frames: Result<Vec<Frame>> = install(|| {
+            all_images
+                .into_par_iter()
+                .map(|image| {
+                    task.check()?;
+                    let cursor = Cursor::new(&image.0);
+                    let img = ImageReader::new(cursor).with_guessed_format()?.decode()?;
+                    let resized_image = task
+                        .silent()
+                        .run(|| resize_rgba(&img.into_rgba8(), width, height, filter))?;
+                    let completed = images_done.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    task.report(completed + 1, Some(total));
+                    Ok(Frame::from_parts(
+                        resized_image,
+                        0,
+                        0,
+                        image::Delay::from_saturating_duration(frame_duration),
+                    ))
+                })
+                .collect()
+        });
+
+        let frames = frames?.into_iter().map(|frame| {
+            task.check()?;
+            Ok(frame)
+        });
+        encode_gif_stream(frames).map(Self)
+    }
+
+    /// 将另一张图片叠加到图像上
+    ///
+    /// 叠加位置为锚点位置加上偏移量，超出图像的部分会被裁掉。
+    /// 底图和叠加图都可以是动图，动图输出 GIF，帧间隔跟随底图，底图为静态图时跟随叠加图。
+    ///
+    /// 两者都是动图时会先解码一遍统计帧数，输出帧数为两者帧数的最小公倍数，两段动画都能完整循环；
+    /// 最小公倍数超过 1000 帧时改为较长一方的帧数，较短的一方在结尾处从头循环。
+    ///
+    /// # 参数
+    /// - `other`: 叠加的图片
+    /// - `x`: 相对锚点的 X 偏移量，可以为负数
+    /// - `y`: 相对锚点的 Y 偏移量，可以为负数
+    /// - `opacity`: 叠加图的不透明度，取值 0.0 ~ 1.0
+    /// - `options`: 叠加选项，默认以左上角为锚点
+    pub fn overlay(
+        &self,
+        other: &Image,
+        x: i64,
+        y: i64,
+        opacity: f32,
+        options: Option<OverlayOptions>,
+    ) -> Result<Self> {
+        /// 按最小公倍数循环时允许的最大输出帧数
+        const MAX_CYCLE_FRAMES: usize = 1000;
+
+        let options = options.unwrap_or_default();
+        let compose = |mut base: RgbaImage, top: &RgbaImage| {
+            let (left, upper) = options.anchor.position(base.dimensions(), top.dimensions());
+            composite(&mut base, top, left + x, upper + y, opacity, options.blend);
+            base
+        };
+
+        let Some(overlay_frames) = decode_frames(&other.0)? else {
+            let cursor = Cursor::new(&other.0);
+            let reader = ImageReader::new(cursor).with_guessed_format()?;
+            let top = reader.decode()?.into_rgba8();
+            return self.map_frames(|base| Ok(compose(base, &top)));
+        };
+
+        let task = Task::current();
+        let Some(base_frames) = decode_frames(&self.0)? else {
+            let cursor = Cursor::new(&self.0);
+            let reader = ImageReader::new(cursor).with_guessed_format()?;
+            let base = reader.decode()?.into_rgba8();
+            let frames = overlay_frames.map(|frame| {
+                let frame = frame?;
+                let delay = frame.delay();
                 Ok(Frame::from_parts(
-                    resized_image.into_rgba8(),
+                    compose(base.clone(), frame.buffer()),
                     0,
                     0,
-                    image::Delay::from_saturating_duration(frame_duration),
+                    delay,
                 ))
-            })
-            .collect();
+            });
+            return encode_gif_stream(task.track(frames, None)).map(Self);
+        };
+
+        let silent = task.silent();
+        let count = |frames: image::Frames| {
+            let frames = frames.map(|frame| frame.map(drop).map_err(Error::from));
+            silent
+                .track(frames, None)
+                .try_fold(0usize, |count, frame| frame.map(|()| count + 1))
+        };
+        let base_count = count(base_frames)?;
+        let overlay_count = count(overlay_frames)?;
+        if base_count == 0 || overlay_count == 0 {
+            return Err(Error::Other("animation has no frames".to_string()));
+        }
+        let (mut a, mut b) = (base_count, overlay_count);
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        let total = (base_count / a)
+            .checked_mul(overlay_count)
+            .filter(|&cycle| cycle <= MAX_CYCLE_FRAMES)
+            .unwrap_or(base_count.max(overlay_count));
+
+        let (Some(bases), Some(tops)) = (cycle_frames(&self.0)?, cycle_frames(&other.0)?) else {
+            return Err(animation_unsupported());
+        };
+        let frames = bases.zip(tops).take(total).map(|(base, top)| {
+            let (base, top) = (base?, top?);
+            let (left, upper, delay) = (base.left(), base.top(), base.delay());
+            Ok(Frame::from_parts(
+                compose(base.into_buffer(), top.buffer()),
+                left,
+                upper,
+                delay,
+            ))
+        });
+        encode_gif_stream(task.track(frames, Some(total as u32))).map(Self)
+    }
+
+    /// 添加水印
+    ///
+    /// 动图会逐帧添加水印，帧间隔保持不变。
+    ///
+    /// # 参数
+    /// - `mark`: 水印内容，可以是图片或文字
+    /// - `options`: 水印选项，默认右下角、边距 16、不透明度 0.5
+    pub fn watermark(&self, mark: &Watermark, options: Option<WatermarkOptions>) -> Result<Self> {
+        let options = options.unwrap_or_default();
+        let cursor = Cursor::new(&self.0);
+        let (width, height) = ImageReader::new(cursor)
+            .with_guessed_format()?
+            .into_dimensions()?;
+
+        let mut mark = match mark {
+            Watermark::Image(image) => {
+                let cursor = Cursor::new(&image.0);
+                let reader = ImageReader::new(cursor).with_guessed_format()?;
+                reader.decode()?.into_rgba8()
+            }
+            Watermark::Text {
+                text,
+                font,
+                size,
+                color,
+            } => render_text(text, font, *size, *color)?,
+        };
+        if let Some(scale) = options.scale {
+            let mark_width = ((width as f32 * scale).round() as u32).max(1);
+            let mark_height = ((mark.height() as u64 * mark_width as u64
+                / mark.width().max(1) as u64) as u32)
+                .max(1);
+            mark = Task::current()
+                .silent()
+                .run(|| resize_rgba(&mark, mark_width, mark_height, options.filter.into()))?;
+        }
+
+        let margin = options.margin as i64;
+        let (layer, x, y) = if options.tiled {
+            // 水印逆时针旋转 45 度后交错平铺，铺满整张图像
+            let theta = -std::f32::consts::FRAC_PI_4;
+            let (sin, cos) = theta.sin_cos();
+            let (mark_width, mark_height) = (mark.width() as f32, mark.height() as f32);
+            let tile_width = (mark_height * sin.abs() + mark_width * cos.abs()).ceil() as u32;
+            let tile_height = (mark_height * cos.abs() + mark_width * sin.abs()).ceil() as u32;
+            let projection =
+                Projection::translate(tile_width as f32 / 2.0, tile_height as f32 / 2.0)
+                    * Projection::rotate(theta)
+                    * Projection::translate(-mark_width / 2.0, -mark_height / 2.0);
+            // 旋转水印只是准备工作，不报告按行进度
+            let tile = Task::current().silent().run(|| {
+                warp(
+                    &mark,
+                    projection,
+                    tile_width,
+                    tile_height,
+                    Interpolation::Bilinear,
+                    Fill::Transparent,
+                )
+            })?;
+            let step_x = tile.width() as i64 + margin;
+            let step_y = tile.height() as i64 + margin;
+            let mut layer = RgbaImage::new(width, height);
+            for (row, top) in (-step_y..height as i64)
+                .step_by(step_y as usize)
+                .enumerate()
+            {
+                let offset = if row % 2 == 1 { step_x / 2 } else { 0 };
+                for left in (-step_x + offset..width as i64).step_by(step_x as usize) {
+                    image::imageops::overlay(&mut layer, &tile, left, top);
+                }
+            }
+            (layer, 0, 0)
+        } else {
+            let inner = (
+                width.saturating_sub(options.margin.saturating_mul(2)),
+                height.saturating_sub(options.margin.saturating_mul(2)),
+            );
+            let (x, y) = options.position.position(inner, mark.dimensions());
+            (mark, x + margin, y + margin)
+        };
+
+        self.map_frames(|mut image| {
+            composite(&mut image, &layer, x, y, options.opacity, BlendMode::Normal);
+            Ok(image)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CancelToken;
+
+    fn encode(image: &RgbaImage) -> Image {
+        let mut buffer = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut buffer), image::ImageFormat::Png)
+            .unwrap();
+        Image::from_bytes(buffer)
+    }
+
+    fn decode(image: &Image) -> RgbaImage {
+        image::load_from_memory(&image.0).unwrap().into_rgba8()
+    }
+
+    #[test]
+    fn split_pages_cuts_on_uniform_rows() {
+        // 内容行左半黑右半白，第 90 ~ 109 行为纯白空隙
+        let image = RgbaImage::from_fn(20, 300, |x, y| {
+            if (90..110).contains(&y) || x >= 10 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        });
+        let image = encode(&image);
+        let heights = |pages: Vec<Image>| -> Vec<u32> {
+            pages.iter().map(|page| decode(page).height()).collect()
+        };
+
+        // 切在空隙中间，后半段没有空隙时按最大高度切分
+        assert_eq!(
+            heights(image.split_pages(150, 0).unwrap()),
+            vec![100, 150, 50]
+        );
+        assert_eq!(
+            heights(image.split_pages(150, 10).unwrap()),
+            vec![100, 150, 70]
+        );
+        // 空隙在前半段时不使用
+        assert_eq!(heights(image.split_pages(250, 0).unwrap()), vec![250, 50]);
+
+        assert!(image.split_pages(0, 0).is_err());
+        assert!(image.split_pages(10, 10).is_err());
+    }
+
+    #[test]
+    fn trim_removes_border() {
+        let mut image = RgbaImage::from_pixel(12, 9, Rgba([255, 255, 255, 255]));
+        for y in 2..5 {
+            for x in 3..10 {
+                image.put_pixel(x, y, Rgba([(x * 20) as u8, 0, 0, 255]));
+            }
+        }
+        let trimmed = decode(&encode(&image).trim(0).unwrap());
+        assert_eq!(trimmed, image.view(3, 2, 7, 3).to_image());
+
+        let blank = RgbaImage::from_pixel(4, 4, Rgba([0, 0, 0, 0]));
+        assert_eq!(decode(&encode(&blank).trim(0).unwrap()), blank);
+    }
+
+    #[test]
+    fn rotate_right_angles_exactly() {
+        let image = RgbaImage::from_fn(3, 2, |x, y| Rgba([x as u8 * 80, y as u8 * 200, 7, 255]));
+        let source = encode(&image);
+        let expand = Some(TransformOptions {
+            expand: true,
+            ..Default::default()
+        });
+
+        let rotate = |angle: f32| decode(&source.rotate(angle, expand).unwrap());
+        assert_eq!(rotate(90.0), image::imageops::rotate90(&image));
+        assert_eq!(rotate(-270.0), image::imageops::rotate90(&image));
+        assert_eq!(rotate(180.0), image::imageops::rotate180(&image));
+        assert_eq!(rotate(270.0), image::imageops::rotate270(&image));
+        assert_eq!(rotate(360.0), image);
+        // 不扩展画布时保持原尺寸
+        assert_eq!(
+            decode(&source.rotate(90.0, None).unwrap()).dimensions(),
+            (3, 2)
+        );
+    }
 
-        encode_gif(frames?).map(Self)
+    #[test]
+    fn cancelled_operations_return_error() {
+        let source = encode(&RgbaImage::from_pixel(64, 64, Rgba([1, 2, 3, 255])));
+        let token = CancelToken::new();
+        token.cancel();
+        let task = Task::new().with_cancel_token(token);
+
+        assert!(matches!(
+            task.run(|| source.rotate(33.0, None)),
+            Err(Error::Cancelled)
+        ));
+        assert!(matches!(
+            task.run(|| source.resize(Some(32), Some(32), None)),
+            Err(Error::Cancelled)
+        ));
+        assert!(matches!(
+            task.run(|| source.blend_color(Rgba([255, 0, 0, 255]), None, 1.0)),
+            Err(Error::Cancelled)
+        ));
     }
 }
@@ -1,3 +1,4 @@
+mod blend;
 mod error;
 #[doc(inline)]
 pub use error::Error;
@@ -5,6 +6,12 @@ mod common;
 mod image;
 #[doc(inline)]
 pub use image::*;
+mod parallel;
+#[doc(inline)]
+pub use parallel::{Parallelism, parallelism, set_parallelism};
+mod task;
+#[doc(inline)]
+pub use task::{CancelToken, Progress, Task};
 mod types;
 #[doc(inline)]
 pub use types::*;
@@ -0,0 +1,110 @@
+use crate::task::Task;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::{Arc, RwLock};
+
+/// 并行策略
+///
+/// 决定 `mirage`、`merge_gif`、`rotate` 等并行操作使用的线程。
+#[derive(Debug, Clone)]
+pub enum Parallelism {
+    /// 使用 rayon 全局线程池
+    Global,
+    /// 使用自定义线程池
+    Pool(Arc<ThreadPool>),
+    /// 严格单线程，每次调用都在各自独立的一个工作线程上依次完成
+    Single,
+}
+
+impl Default for Parallelism {
+    /// 不支持线程的 wasm 目标默认单线程，其余平台默认使用全局线程池
+    fn default() -> Self {
+        if cfg!(all(target_family = "wasm", not(target_feature = "atomics"))) {
+            Self::Single
+        } else {
+            Self::Global
+        }
+    }
+}
+
+impl Parallelism {
+    /// 创建指定线程数的并行策略
+    ///
+    /// # 参数
+    /// - `count`: 线程数，为 0 时使用全局线程池，为 1 时严格单线程
+    pub fn threads(count: usize) -> crate::Result<Self> {
+        match count {
+            0 => Ok(Self::Global),
+            1 => Ok(Self::Single),
+            count => {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(count)
+                    .thread_name(|index| format!("piccy-{index}"))
+                    .build()?;
+                Ok(Self::Pool(Arc::new(pool)))
+            }
+        }
+    }
+}
+
+static PARALLELISM: RwLock<Option<Parallelism>> = RwLock::new(None);
+
+/// 单线程模式工作线程的名称
+const SINGLE_THREAD_NAME: &str = "piccy-single";
+
+/// 为单次调用创建单线程池
+///
+/// 每次调用独占一个线程，并发调用之间互不阻塞。
+/// 无法创建线程时（例如不支持线程的 wasm 目标）为 `None`，直接在调用线程上执行。
+fn single_thread_pool() -> Option<ThreadPool> {
+    ThreadPoolBuilder::new()
+        .num_threads(1)
+        .thread_name(|_| SINGLE_THREAD_NAME.to_string())
+        .build()
+        .ok()
+}
+
+/// 设置全局并行策略
+///
+/// 该设置作用于整个进程，会影响之后所有线程上的调用。
+///
+/// # 参数
+/// - `parallelism`: 并行策略
+pub fn set_parallelism(parallelism: Parallelism) {
+    let mut current = PARALLELISM.write().unwrap_or_else(|e| e.into_inner());
+    *current = Some(parallelism);
+}
+
+/// 获取当前的并行策略
+pub fn parallelism() -> Parallelism {
+    PARALLELISM
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_default()
+}
+
+/// 在当前并行策略对应的线程池中执行
+///
+/// 调用线程上的任务上下文会随之传递到线程池中。
+pub(crate) fn install<R, F>(op: F) -> R
+where
+    F: FnOnce() -> R + Send,
+    R: Send,
+{
+    match parallelism() {
+        Parallelism::Global => op(),
+        Parallelism::Pool(pool) => {
+            let task = Task::current();
+            pool.install(move || task.run(op))
+        }
+        // 已经在单线程池中时直接执行，避免嵌套调用再创建线程
+        Parallelism::Single if std::thread::current().name() == Some(SINGLE_THREAD_NAME) => op(),
+        Parallelism::Single => match single_thread_pool() {
+            Some(pool) => {
+                let task = Task::current();
+                pool.install(move || task.run(op))
+            }
+            None => op(),
+        },
+    }
+}
@@ -0,0 +1,172 @@
+use crate::error::Error;
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 取消令牌
+///
+/// 克隆得到的令牌共享同一个取消状态，可以在其他线程中调用 [`CancelToken::cancel`]。
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// 创建取消令牌
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 取消任务
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// 是否已取消
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// 任务进度
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Progress {
+    /// 已完成的步骤数，按帧或按行计
+    pub completed: u32,
+    /// 总步骤数，逐帧处理动图时无法预知总数，为 `None`
+    pub total: Option<u32>,
+}
+
+type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// 任务上下文
+///
+/// 通过 [`Task::run`] 执行的图像操作会在每帧或每行处理前检查取消令牌，并回调进度。
+/// 任务被取消时返回 [`Error::Cancelled`]。
+#[derive(Clone, Default)]
+pub struct Task {
+    token: Option<CancelToken>,
+    progress: Option<ProgressCallback>,
+}
+
+impl fmt::Debug for Task {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Task")
+            .field("token", &self.token)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+thread_local! {
+    static CURRENT_TASK: RefCell<Option<Task>> = const { RefCell::new(None) };
+}
+
+impl Task {
+    /// 创建任务上下文
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 设置取消令牌
+    ///
+    /// # 参数
+    /// - `token`: 取消令牌
+    pub fn with_cancel_token(mut self, token: CancelToken) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// 设置进度回调
+    ///
+    /// # 参数
+    /// - `callback`: 进度回调，可能在工作线程中被调用
+    pub fn with_progress(mut self, callback: impl Fn(Progress) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// 在任务上下文中执行图像操作
+    ///
+    /// # 参数
+    /// - `op`: 需要执行的操作
+    pub fn run<R>(&self, op: impl FnOnce() -> R) -> R {
+        struct Restore(Option<Task>);
+
+        impl Drop for Restore {
+            fn drop(&mut self) {
+                CURRENT_TASK.with(|current| *current.borrow_mut() = self.0.take());
+            }
+        }
+
+        let previous = CURRENT_TASK.with(|current| current.replace(Some(self.clone())));
+        let _restore = Restore(previous);
+        op()
+    }
+
+    /// 获取当前线程的任务上下文
+    pub(crate) fn current() -> Self {
+        CURRENT_TASK
+            .with(|current| current.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// 是否已取消
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.token.as_ref().is_some_and(CancelToken::is_cancelled)
+    }
+
+    /// 检查取消状态
+    pub(crate) fn check(&self) -> crate::Result<()> {
+        if self.is_cancelled() {
+            Err(Error::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 报告进度
+    pub(crate) fn report(&self, completed: u32, total: Option<u32>) {
+        if let Some(progress) = &self.progress {
+            progress(Progress { completed, total });
+        }
+    }
+
+    /// 不报告进度的任务上下文，只保留取消令牌
+    ///
+    /// 逐帧处理时用于执行每一帧的操作，避免帧内的按行进度与帧进度混在一起。
+    pub(crate) fn silent(&self) -> Self {
+        Self {
+            token: self.token.clone(),
+            progress: None,
+        }
+    }
+
+    /// 逐项检查取消状态并报告进度
+    ///
+    /// 在取出下一项之前检查取消状态，任务被取消后不会再处理新的项。
+    pub(crate) fn track<I, T>(
+        &self,
+        items: I,
+        total: Option<u32>,
+    ) -> impl Iterator<Item = crate::Result<T>> + use<I, T>
+    where
+        I: IntoIterator<Item = crate::Result<T>>,
+    {
+        let task = self.clone();
+        let mut items = items.into_iter();
+        let mut completed = 0;
+        let mut cancelled = false;
+        std::iter::from_fn(move || {
+            if cancelled {
+                return None;
+            }
+            if let Err(error) = task.check() {
+                cancelled = true;
+                return Some(Err(error));
+            }
+            let item = items.next()?;
+            completed += 1;
+            Some(item.inspect(|_| task.report(completed, total)))
+        })
+    }
+}
@@ -1,4 +1,7 @@
-use image::Frame;
+use crate::error::Error;
+use bytes::Bytes;
+use image::imageops::FilterType;
+use image::{Frame, Frames, Rgba};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq)]
@@ -50,6 +53,31 @@ impl From<&Vec<Frame>> for AnimationInfo {
     }
 }
 
+impl TryFrom<Frames<'_>> for AnimationInfo {
+    type Error = Error;
+
+    /// 逐帧统计动图信息，不会同时保留多帧数据
+    fn try_from(frames: Frames<'_>) -> Result<Self, Self::Error> {
+        let mut frame_count = 0u32;
+        let mut total_delay = 0.0f32;
+        for frame in frames {
+            let frame = frame?;
+            frame_count += 1;
+            total_delay += frame.delay().numer_denom_ms().0 as f32;
+        }
+        let frame_delay = if frame_count > 0 {
+            total_delay / frame_count as f32
+        } else {
+            0.0
+        };
+
+        Ok(Self {
+            frame_count,
+            frame_delay,
+        })
+    }
+}
+
 #[derive(Debug, Copy, Clone, Deserialize, Serialize, PartialEq)]
 pub struct ImageInfo {
     /// 图片大小，单位为字节
@@ -73,6 +101,12 @@ pub enum FlipMode {
     Horizontal,
     /// 垂直翻转
     Vertical,
+    /// 同时水平和垂直翻转，等同于旋转 180 度
+    Both,
+    /// 沿主对角线翻转，即行列互换
+    Transpose,
+    /// 沿副对角线翻转
+    Transverse,
 }
 
 /// 图片拼接模式
@@ -83,6 +117,403 @@ pub enum MergeMode {
     Horizontal,
     /// 垂直拼接
     Vertical,
+    /// 网格拼接，每个单元格与第一张图片尺寸相同，图片等比缩放后居中放入单元格
+    Grid {
+        /// 列数，不指定时按图片数量排成近似正方形
+        columns: Option<u32>,
+    },
+}
+
+/// 锚点位置
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Gravity {
+    /// 居中
+    #[default]
+    Center,
+    /// 顶部居中
+    Top,
+    /// 底部居中
+    Bottom,
+    /// 左侧居中
+    Left,
+    /// 右侧居中
+    Right,
+    /// 左上角
+    TopLeft,
+    /// 右上角
+    TopRight,
+    /// 左下角
+    BottomLeft,
+    /// 右下角
+    BottomRight,
+}
+
+impl Gravity {
+    /// 计算内容按锚点对齐到容器时左上角的坐标，内容大于容器时坐标为负
+    pub(crate) fn position(self, container: (u32, u32), content: (u32, u32)) -> (i64, i64) {
+        let dx = container.0 as i64 - content.0 as i64;
+        let dy = container.1 as i64 - content.1 as i64;
+        let (fx, fy) = match self {
+            Gravity::Center => (1, 1),
+            Gravity::Top => (1, 0),
+            Gravity::Bottom => (1, 2),
+            Gravity::Left => (0, 1),
+            Gravity::Right => (2, 1),
+            Gravity::TopLeft => (0, 0),
+            Gravity::TopRight => (2, 0),
+            Gravity::BottomLeft => (0, 2),
+            Gravity::BottomRight => (2, 2),
+        };
+        (dx * fx / 2, dy * fy / 2)
+    }
+}
+
+/// 拼接时在交叉轴上的对齐方式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Align {
+    /// 顶部或左侧对齐
+    Start,
+    /// 居中对齐
+    #[default]
+    Center,
+    /// 底部或右侧对齐
+    End,
+}
+
+impl Align {
+    /// 计算内容在可用空间中的偏移量
+    pub(crate) fn offset(self, space: u32, size: u32) -> i64 {
+        let free = space as i64 - size as i64;
+        match self {
+            Align::Start => 0,
+            Align::Center => free / 2,
+            Align::End => free,
+        }
+    }
+}
+
+/// 缩放适配模式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ResizeFit {
+    /// 拉伸到目标尺寸，不保持宽高比
+    #[default]
+    Fill,
+    /// 保持宽高比铺满目标尺寸，超出部分按锚点裁剪
+    Cover,
+    /// 保持宽高比完整放入目标尺寸，空白部分使用背景色填充
+    Contain,
+    /// 保持宽高比缩放到不超过目标尺寸
+    Inside,
+    /// 保持宽高比缩放到不小于目标尺寸
+    Outside,
+}
+
+impl ResizeFit {
+    /// 计算输出画布尺寸与图像缩放后的尺寸
+    ///
+    /// 只指定一个方向时按原图宽高比计算另一个方向，都不指定时保持原尺寸。
+    pub(crate) fn dimensions(
+        self,
+        source: (u32, u32),
+        width: Option<u32>,
+        height: Option<u32>,
+    ) -> ((u32, u32), (u32, u32)) {
+        let source_width = source.0.max(1) as f64;
+        let source_height = source.1.max(1) as f64;
+        let scaled = |scale: f64| {
+            (
+                ((source_width * scale).round() as u32).max(1),
+                ((source_height * scale).round() as u32).max(1),
+            )
+        };
+
+        match (width, height) {
+            (None, None) => (source, source),
+            (Some(width), None) => {
+                let size = (width, scaled(width as f64 / source_width).1);
+                (size, size)
+            }
+            (None, Some(height)) => {
+                let size = (scaled(height as f64 / source_height).0, height);
+                (size, size)
+            }
+            (Some(width), Some(height)) => {
+                let scale_x = width as f64 / source_width;
+                let scale_y = height as f64 / source_height;
+                match self {
+                    ResizeFit::Fill => ((width, height), (width, height)),
+                    ResizeFit::Cover => ((width, height), scaled(scale_x.max(scale_y))),
+                    ResizeFit::Contain => ((width, height), scaled(scale_x.min(scale_y))),
+                    ResizeFit::Inside => {
+                        let size = scaled(scale_x.min(scale_y));
+                        (size, size)
+                    }
+                    ResizeFit::Outside => {
+                        let size = scaled(scale_x.max(scale_y));
+                        (size, size)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// 重采样滤波器
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum ResampleFilter {
+    /// 最近邻，适合像素画
+    Nearest,
+    /// 双线性
+    Bilinear,
+    /// 双三次（Catmull-Rom）
+    CatmullRom,
+    /// 高斯
+    Gaussian,
+    /// Lanczos3
+    #[default]
+    Lanczos3,
+}
+
+impl From<ResampleFilter> for FilterType {
+    fn from(filter: ResampleFilter) -> Self {
+        match filter {
+            ResampleFilter::Nearest => FilterType::Nearest,
+            ResampleFilter::Bilinear => FilterType::Triangle,
+            ResampleFilter::CatmullRom => FilterType::CatmullRom,
+            ResampleFilter::Gaussian => FilterType::Gaussian,
+            ResampleFilter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+/// 缩放选项
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ResizeOptions {
+    /// 适配模式，默认拉伸
+    pub fit: ResizeFit,
+    /// 裁剪或留白时的锚点，默认居中
+    pub gravity: Gravity,
+    /// 留白部分的背景色，默认透明
+    pub background: Rgba<u8>,
+    /// 重采样滤波器，默认 Lanczos3
+    pub filter: ResampleFilter,
+}
+
+impl Default for ResizeOptions {
+    fn default() -> Self {
+        Self {
+            fit: ResizeFit::default(),
+            gravity: Gravity::default(),
+            background: Rgba([0, 0, 0, 0]),
+            filter: ResampleFilter::default(),
+        }
+    }
+}
+
+/// 拼接选项
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MergeOptions {
+    /// 图片之间的间距，默认 0
+    pub spacing: u32,
+    /// 背景色，默认透明
+    pub background: Rgba<u8>,
+    /// 交叉轴上的对齐方式，默认居中
+    pub align: Align,
+    /// 是否缩放到统一尺寸，为 `false` 时保持原尺寸并用背景色填充空白，默认缩放
+    pub scale: bool,
+    /// 缩放时使用的重采样滤波器，默认双线性
+    pub filter: ResampleFilter,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            spacing: 0,
+            background: Rgba([0, 0, 0, 0]),
+            align: Align::default(),
+            scale: true,
+            filter: ResampleFilter::Bilinear,
+        }
+    }
+}
+
+/// 插值方式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// 最近邻
+    Nearest,
+    /// 双线性
+    #[default]
+    Bilinear,
+    /// 双三次
+    Bicubic,
+}
+
+impl From<Interpolation> for imageproc::geometric_transformations::Interpolation {
+    fn from(interpolation: Interpolation) -> Self {
+        match interpolation {
+            Interpolation::Nearest => Self::Nearest,
+            Interpolation::Bilinear => Self::Bilinear,
+            Interpolation::Bicubic => Self::Bicubic,
+        }
+    }
+}
+
+/// 画布空白部分的填充方式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Fill {
+    /// 透明
+    #[default]
+    Transparent,
+    /// 纯色
+    Color(Rgba<u8>),
+    /// 延伸边缘像素
+    Edge,
+    /// 镜像边缘像素
+    Mirror,
+}
+
+/// 几何变换选项
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct TransformOptions {
+    /// 是否扩展画布以容纳完整的变换结果，默认保持原画布尺寸
+    pub expand: bool,
+    /// 插值方式，默认双线性
+    pub interpolation: Interpolation,
+    /// 空白部分的填充方式，默认透明
+    pub fill: Fill,
+}
+
+/// 混合模式
+///
+/// 公式参考 W3C Compositing and Blending Level 1。
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// 正常
+    #[default]
+    Normal,
+    /// 正片叠底
+    Multiply,
+    /// 滤色
+    Screen,
+    /// 叠加
+    Overlay,
+    /// 变暗
+    Darken,
+    /// 变亮
+    Lighten,
+    /// 颜色减淡
+    ColorDodge,
+    /// 颜色加深
+    ColorBurn,
+    /// 柔光
+    SoftLight,
+    /// 强光
+    HardLight,
+    /// 差值
+    Difference,
+    /// 排除
+    Exclusion,
+    /// 色相
+    Hue,
+    /// 饱和度
+    Saturation,
+    /// 颜色
+    Color,
+    /// 明度
+    Luminosity,
+}
+
+/// 叠加选项
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct OverlayOptions {
+    /// 偏移量的参照锚点，默认左上角
+    pub anchor: Gravity,
+    /// 混合模式，默认正常
+    pub blend: BlendMode,
+}
+
+impl Default for OverlayOptions {
+    fn default() -> Self {
+        Self {
+            anchor: Gravity::TopLeft,
+            blend: BlendMode::default(),
+        }
+    }
+}
+
+/// 蒙版通道
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum MaskChannel {
+    /// 透明度通道
+    #[default]
+    Alpha,
+    /// 亮度，白色为不透明，黑色为透明
+    Luminance,
+    /// 红色通道
+    Red,
+    /// 绿色通道
+    Green,
+    /// 蓝色通道
+    Blue,
+}
+
+/// 发光模式
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum GlowMode {
+    /// 外发光
+    #[default]
+    Outer,
+    /// 内发光
+    Inner,
+}
+
+/// 水印内容
+#[derive(Clone)]
+pub enum Watermark {
+    /// 图片水印，动图只使用第一帧
+    Image(crate::Image),
+    /// 文字水印
+    Text {
+        /// 文字内容，不支持换行
+        text: String,
+        /// TrueType 或 OpenType 字体文件数据
+        font: Bytes,
+        /// 字号，单位为像素
+        size: f32,
+        /// 文字颜色
+        color: Rgba<u8>,
+    },
+}
+
+/// 水印选项
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct WatermarkOptions {
+    /// 水印位置，默认右下角，平铺时忽略
+    pub position: Gravity,
+    /// 水印与图像边缘的距离，平铺时为水印之间的间距，默认 16
+    pub margin: u32,
+    /// 水印的不透明度，取值 0.0 ~ 1.0，默认 0.5
+    pub opacity: f32,
+    /// 水印宽度占图像宽度的比例，默认保持水印原尺寸
+    pub scale: Option<f32>,
+    /// 是否沿对角线平铺水印
+    pub tiled: bool,
+    /// 缩放水印时使用的重采样滤波器，默认 Lanczos3
+    pub filter: ResampleFilter,
+}
+
+impl Default for WatermarkOptions {
+    fn default() -> Self {
+        Self {
+            position: Gravity::BottomRight,
+            margin: 16,
+            opacity: 0.5,
+            scale: None,
+            tiled: false,
+            filter: ResampleFilter::Lanczos3,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -92,3 +523,78 @@ pub enum ImageFormat {
     WebP,
     Gif,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resize_fit_dimensions() {
+        let source = (400, 200);
+        assert_eq!(
+            ResizeFit::Fill.dimensions(source, None, None),
+            ((400, 200), (400, 200))
+        );
+        assert_eq!(
+            ResizeFit::Fill.dimensions(source, Some(100), None),
+            ((100, 50), (100, 50))
+        );
+        assert_eq!(
+            ResizeFit::Cover.dimensions(source, None, Some(100)),
+            ((200, 100), (200, 100))
+        );
+
+        let cases = [
+            (ResizeFit::Fill, ((100, 100), (100, 100))),
+            (ResizeFit::Cover, ((100, 100), (200, 100))),
+            (ResizeFit::Contain, ((100, 100), (100, 50))),
+            (ResizeFit::Inside, ((100, 50), (100, 50))),
+            (ResizeFit::Outside, ((200, 100), (200, 100))),
+        ];
+        for (fit, expected) in cases {
+            assert_eq!(
+                fit.dimensions(source, Some(100), Some(100)),
+                expected,
+                "{fit:?}"
+            );
+        }
+
+        // 缩放后的边长至少为 1
+        assert_eq!(
+            ResizeFit::Inside.dimensions((1000, 1), Some(10), Some(10)),
+            ((10, 1), (10, 1))
+        );
+    }
+
+    #[test]
+    fn gravity_position() {
+        let cases = [
+            (Gravity::TopLeft, (0, 0)),
+            (Gravity::Top, (40, 0)),
+            (Gravity::TopRight, (80, 0)),
+            (Gravity::Left, (0, 20)),
+            (Gravity::Center, (40, 20)),
+            (Gravity::Right, (80, 20)),
+            (Gravity::BottomLeft, (0, 40)),
+            (Gravity::Bottom, (40, 40)),
+            (Gravity::BottomRight, (80, 40)),
+        ];
+        for (gravity, expected) in cases {
+            assert_eq!(
+                gravity.position((100, 50), (20, 10)),
+                expected,
+                "{gravity:?}"
+            );
+        }
+        // 内容比容器大时偏移为负
+        assert_eq!(Gravity::Center.position((10, 10), (20, 30)), (-5, -10));
+    }
+
+    #[test]
+    fn align_offset() {
+        assert_eq!(Align::Start.offset(100, 30), 0);
+        assert_eq!(Align::Center.offset(100, 30), 35);
+        assert_eq!(Align::End.offset(100, 30), 70);
+        assert_eq!(Align::End.offset(10, 30), -20);
+    }
+}
@@ -2,11 +2,15 @@ mod types;
 
 type Result<T> = napi::Result<T>;
 
-use crate::types::{ImageFormat, Rgb};
+use crate::types::{ImageFormat, Point, Rgb, Rgba};
 use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
 use std::time::Duration;
-use types::{FlipMode, ImageInfo, MergeMode};
+use types::{
+    BlendMode, FillMode, FlipMode, GlowMode, Gravity, ImageInfo, MaskChannel, MergeMode,
+    MergeOptions, OverlayOptions, ResampleFilter, ResizeOptions, TextWatermark, TransformOptions,
+    WatermarkOptions,
+};
 
 /// 图像处理类
 #[napi]
@@ -136,16 +140,163 @@ impl Image {
         Ok(Self { inner })
     }
 
+    /// 裁剪图像，超出图像范围的部分会被截去
+    ///
+    /// # 参数
+    /// - `x`: 裁剪的左上角 X 坐标
+    /// - `y`: 裁剪的左上角 Y 坐标
+    /// - `width`: 裁剪的宽度
+    /// - `height`: 裁剪的高度
+    #[napi]
+    pub fn crop_clamped(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        let inner = self
+            .inner
+            .crop_clamped(x, y, width, height)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 按相对坐标裁剪图像
+    ///
+    /// # 参数
+    /// - `x`: 左上角 X 坐标占图像宽度的比例，取值 0 ~ 1
+    /// - `y`: 左上角 Y 坐标占图像高度的比例，取值 0 ~ 1
+    /// - `width`: 裁剪宽度占图像宽度的比例，取值 0 ~ 1
+    /// - `height`: 裁剪高度占图像高度的比例，取值 0 ~ 1
+    #[napi]
+    pub fn crop_relative(&self, x: f64, y: f64, width: f64, height: f64) -> Result<Self> {
+        let inner = self
+            .inner
+            .crop_relative(x as f32, y as f32, width as f32, height as f32)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 按宽高比裁剪出最大的区域
+    ///
+    /// # 参数
+    /// - `width`: 宽高比中的宽
+    /// - `height`: 宽高比中的高
+    /// - `gravity`: 裁剪区域的锚点，默认居中
+    #[napi]
+    pub fn crop_aspect(&self, width: u32, height: u32, gravity: Option<Gravity>) -> Result<Self> {
+        let inner = self
+            .inner
+            .crop_aspect(width, height, gravity.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 智能裁剪，保留信息量最大的区域
+    ///
+    /// # 参数
+    /// - `width`: 输出宽度
+    /// - `height`: 输出高度
+    /// - `filter`: 重采样滤波器，默认 Lanczos3
+    #[napi]
+    pub fn smart_crop(
+        &self,
+        width: u32,
+        height: u32,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .smart_crop(width, height, filter.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 裁掉纯色边框或透明边距
+    ///
+    /// # 参数
+    /// - `tolerance`: 各通道允许的颜色差值，默认 0
+    #[napi]
+    pub fn trim(&self, tolerance: Option<u8>) -> Result<Self> {
+        let inner = self
+            .inner
+            .trim(tolerance.unwrap_or_default())
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
     /// 缩放图像
     ///
     /// # 参数
-    /// - `width`: 缩放后的宽度
-    /// - `height`: 缩放后的高度
+    /// - `width`: 缩放后的宽度，不传时按宽高比计算
+    /// - `height`: 缩放后的高度，不传时按宽高比计算
+    /// - `options`: 缩放选项，默认拉伸到目标尺寸
     #[napi]
-    pub fn resize(&self, width: u32, height: u32) -> Result<Image> {
+    pub fn resize(
+        &self,
+        width: Option<u32>,
+        height: Option<u32>,
+        options: Option<ResizeOptions>,
+    ) -> Result<Image> {
         let inner = self
             .inner
-            .resize(width, height)
+            .resize(width, height, options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 扩展图像边距
+    ///
+    /// # 参数
+    /// - `top`: 上边距
+    /// - `right`: 右边距
+    /// - `bottom`: 下边距
+    /// - `left`: 左边距
+    /// - `fill`: 边距的填充方式，默认透明
+    /// - `fill_color`: 纯色填充时使用的颜色，默认黑色
+    #[napi]
+    pub fn pad(
+        &self,
+        top: u32,
+        right: u32,
+        bottom: u32,
+        left: u32,
+        fill: Option<FillMode>,
+        fill_color: Option<Rgba>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .pad(
+                top,
+                right,
+                bottom,
+                left,
+                Some(types::fill(fill, fill_color)),
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 将图像放到更大的画布上
+    ///
+    /// # 参数
+    /// - `width`: 画布宽度
+    /// - `height`: 画布高度
+    /// - `anchor`: 图像在画布中的位置，默认居中
+    /// - `fill`: 空白部分的填充方式，默认透明
+    /// - `fill_color`: 纯色填充时使用的颜色，默认黑色
+    #[napi]
+    pub fn extend_to(
+        &self,
+        width: u32,
+        height: u32,
+        anchor: Option<Gravity>,
+        fill: Option<FillMode>,
+        fill_color: Option<Rgba>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .extend_to(
+                width,
+                height,
+                anchor.map(Into::into),
+                Some(types::fill(fill, fill_color)),
+            )
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(Self { inner })
     }
@@ -153,12 +304,84 @@ impl Image {
     /// 旋转图像
     ///
     /// # 参数
-    /// - `angle`: 旋转的角度（度）
+    /// - `angle`: 顺时针旋转的角度（度）
+    /// - `options`: 变换选项，默认保持原画布尺寸
     #[napi]
-    pub fn rotate(&self, angle: f64) -> Result<Image> {
+    pub fn rotate(&self, angle: f64, options: Option<TransformOptions>) -> Result<Image> {
         let inner = self
             .inner
-            .rotate(angle as f32)
+            .rotate(angle as f32, options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 仿射变换
+    ///
+    /// # 参数
+    /// - `matrix`: 行优先的 2x3 仿射矩阵 `[a, b, c, d, e, f]`
+    /// - `options`: 变换选项，默认保持原画布尺寸
+    #[napi]
+    pub fn affine(&self, matrix: Vec<f64>, options: Option<TransformOptions>) -> Result<Image> {
+        let matrix: [f64; 6] = matrix
+            .try_into()
+            .map_err(|_| napi::Error::from_reason("affine matrix must have 6 elements"))?;
+        let inner = self
+            .inner
+            .affine(matrix.map(|value| value as f32), options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 水平错切
+    ///
+    /// # 参数
+    /// - `angle`: 错切角度（度）
+    /// - `options`: 变换选项，默认保持原画布尺寸
+    #[napi]
+    pub fn shear_x(&self, angle: f64, options: Option<TransformOptions>) -> Result<Image> {
+        let inner = self
+            .inner
+            .shear_x(angle as f32, options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 垂直错切
+    ///
+    /// # 参数
+    /// - `angle`: 错切角度（度）
+    /// - `options`: 变换选项，默认保持原画布尺寸
+    #[napi]
+    pub fn shear_y(&self, angle: f64, options: Option<TransformOptions>) -> Result<Image> {
+        let inner = self
+            .inner
+            .shear_y(angle as f32, options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 透视变换，把原图中的四边形映射到输出画布中的四边形
+    ///
+    /// # 参数
+    /// - `src_quad`: 原图中的四边形顶点
+    /// - `dst_quad`: 输出画布中对应的四边形顶点
+    /// - `width`: 输出宽度
+    /// - `height`: 输出高度
+    #[napi]
+    pub fn warp_perspective(
+        &self,
+        src_quad: Vec<Point>,
+        dst_quad: Vec<Point>,
+        width: u32,
+        height: u32,
+    ) -> Result<Image> {
+        let inner = self
+            .inner
+            .warp_perspective(
+                types::quad(src_quad)?,
+                types::quad(dst_quad)?,
+                piccy_core::Dimensions { width, height },
+            )
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(Self { inner })
     }
@@ -209,15 +432,161 @@ impl Image {
         Ok(Self { inner })
     }
 
+    /// 圆形蒙版
+    #[napi]
+    pub fn mask_circle(&self) -> Result<Self> {
+        let inner = self
+            .inner
+            .mask_circle()
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 圆角蒙版
+    ///
+    /// # 参数
+    /// - `radius`: 圆角半径
+    #[napi]
+    pub fn mask_rounded(&self, radius: f64) -> Result<Self> {
+        let inner = self
+            .inner
+            .mask_rounded(radius as f32)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 应用蒙版
+    ///
+    /// # 参数
+    /// - `mask`: 蒙版图片
+    /// - `channel`: 作为透明度的蒙版通道，默认透明度通道
+    /// - `filter`: 拉伸蒙版时使用的重采样滤波器，默认双线性
+    #[napi]
+    pub fn apply_mask(
+        &self,
+        mask: &Image,
+        channel: Option<MaskChannel>,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .apply_mask(&mask.inner, channel.map(Into::into), filter.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 描边
+    ///
+    /// # 参数
+    /// - `width`: 描边宽度，为 0 时原样返回
+    /// - `color`: 描边颜色，默认白色
+    #[napi]
+    pub fn outline(&self, width: u32, color: Option<Rgba>) -> Result<Self> {
+        let color = color
+            .map(Into::into)
+            .unwrap_or(image::Rgba([255, 255, 255, 255]));
+        let inner = self
+            .inner
+            .outline(width, color)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 投影
+    ///
+    /// # 参数
+    /// - `offset_x`: 阴影的水平偏移量
+    /// - `offset_y`: 阴影的垂直偏移量
+    /// - `blur_radius`: 模糊半径，默认 0
+    /// - `color`: 阴影颜色，默认黑色
+    /// - `opacity`: 阴影的不透明度，取值 0 ~ 1，默认 0.5
+    #[napi]
+    pub fn drop_shadow(
+        &self,
+        offset_x: i32,
+        offset_y: i32,
+        blur_radius: Option<f64>,
+        color: Option<Rgba>,
+        opacity: Option<f64>,
+    ) -> Result<Self> {
+        let color = color.map(Into::into).unwrap_or(image::Rgba([0, 0, 0, 255]));
+        let inner = self
+            .inner
+            .drop_shadow(
+                offset_x,
+                offset_y,
+                blur_radius.unwrap_or_default() as f32,
+                color,
+                opacity.unwrap_or(0.5) as f32,
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 发光
+    ///
+    /// # 参数
+    /// - `radius`: 发光半径
+    /// - `color`: 发光颜色，默认白色
+    /// - `opacity`: 发光的不透明度，取值 0 ~ 1，默认 1
+    /// - `mode`: 发光模式，默认外发光
+    #[napi]
+    pub fn glow(
+        &self,
+        radius: f64,
+        color: Option<Rgba>,
+        opacity: Option<f64>,
+        mode: Option<GlowMode>,
+    ) -> Result<Self> {
+        let color = color
+            .map(Into::into)
+            .unwrap_or(image::Rgba([255, 255, 255, 255]));
+        let inner = self
+            .inner
+            .glow(
+                radius as f32,
+                color,
+                opacity.unwrap_or(1.0) as f32,
+                mode.map(Into::into),
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 按混合模式将纯色混合到图像上
+    ///
+    /// # 参数
+    /// - `color`: 混合的颜色
+    /// - `mode`: 混合模式，默认正常
+    /// - `opacity`: 颜色的不透明度，取值 0 ~ 1，默认 1
+    #[napi]
+    pub fn blend_color(
+        &self,
+        color: Rgba,
+        mode: Option<BlendMode>,
+        opacity: Option<f64>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .blend_color(
+                color.into(),
+                mode.map(Into::into),
+                opacity.unwrap_or(1.0) as f32,
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
     /// 幻影坦克
     ///
     /// # 参数
     /// - `hidden`: 需要隐藏的图片
+    /// - `filter`: 重采样滤波器，默认 Lanczos3
     #[napi]
-    pub fn mirage(&self, hidden: &Image) -> Result<Image> {
+    pub fn mirage(&self, hidden: &Image, filter: Option<ResampleFilter>) -> Result<Image> {
         let inner = self
             .inner
-            .mirage(&hidden.inner)
+            .mirage(&hidden.inner, filter.map(Into::into))
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(Self { inner })
     }
@@ -258,17 +627,62 @@ impl Image {
         Ok(Self { inner })
     }
 
+    /// 切分宫格图
+    ///
+    /// # 参数
+    /// - `rows`: 行数
+    /// - `columns`: 列数
+    ///
+    /// # 返回值
+    /// 按从左到右、从上到下顺序排列的正方形图块数组
+    #[napi]
+    pub fn slice_grid(&self, rows: u32, columns: u32) -> Result<Vec<Image>> {
+        let tiles = self
+            .inner
+            .slice_grid(rows, columns)
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(tiles.into_iter().map(|inner| Self { inner }).collect())
+    }
+
+    /// 将长图切分为多页
+    ///
+    /// # 参数
+    /// - `max_height`: 每页的最大高度
+    /// - `overlap`: 相邻两页重叠的高度，默认 0
+    ///
+    /// # 返回值
+    /// 按从上到下顺序排列的页面数组，切分位置优先选在纯色背景行上
+    #[napi]
+    pub fn split_pages(&self, max_height: u32, overlap: Option<u32>) -> Result<Vec<Image>> {
+        let pages = self
+            .inner
+            .split_pages(max_height, overlap.unwrap_or(0))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(pages.into_iter().map(|inner| Self { inner }).collect())
+    }
+
     /// 拼接图片
     ///
     /// # 参数
     /// - `images`: 需要拼接的其他图片
     /// - `mode`: 拼接模式，默认水平拼接
+    /// - `options`: 拼接选项，默认无间距、透明背景、居中对齐并缩放到统一尺寸
     #[napi]
-    pub fn merge(&self, images: Vec<&Image>, mode: Option<MergeMode>) -> Result<Self> {
+    pub fn merge(
+        &self,
+        images: Vec<&Image>,
+        mode: Option<MergeMode>,
+        options: Option<MergeOptions>,
+    ) -> Result<Self> {
         let inner_images: Vec<&piccy_core::Image> = images.iter().map(|img| &img.inner).collect();
+        let columns = options.as_ref().and_then(|options| options.columns);
+        let mode = mode.map(|mode| match mode {
+            MergeMode::Grid => piccy_core::MergeMode::Grid { columns },
+            mode => mode.into(),
+        });
         let inner = self
             .inner
-            .merge(inner_images, mode.map(Into::into))
+            .merge(inner_images, mode, options.map(Into::into))
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(Self { inner })
     }
@@ -278,14 +692,97 @@ impl Image {
     /// # 参数
     /// - `images`: 其他图片数组
     /// - `duration`: 帧间隔时间（秒），默认 1 秒
+    /// - `filter`: 重采样滤波器，默认 Lanczos3
     #[napi]
-    pub fn merge_gif(&self, images: Vec<&Image>, duration: Option<u32>) -> Result<Self> {
+    pub fn merge_gif(
+        &self,
+        images: Vec<&Image>,
+        duration: Option<u32>,
+        filter: Option<ResampleFilter>,
+    ) -> Result<Self> {
         let inner_images: Vec<&piccy_core::Image> = images.iter().map(|img| &img.inner).collect();
         let delay = duration.map(|d| Duration::from_secs(d as u64));
         let inner = self
             .inner
-            .merge_gif(inner_images, delay)
+            .merge_gif(inner_images, delay, filter.map(Into::into))
             .map_err(|e| napi::Error::from_reason(e.to_string()))?;
         Ok(Self { inner })
     }
+
+    /// 将另一张图片叠加到图像上
+    ///
+    /// # 参数
+    /// - `other`: 叠加的图片
+    /// - `x`: 相对锚点的 X 偏移量，默认 0
+    /// - `y`: 相对锚点的 Y 偏移量，默认 0
+    /// - `opacity`: 叠加图的不透明度，取值 0 ~ 1，默认 1
+    /// - `options`: 叠加选项，默认以左上角为锚点
+    #[napi]
+    pub fn overlay(
+        &self,
+        other: &Image,
+        x: Option<i64>,
+        y: Option<i64>,
+        opacity: Option<f64>,
+        options: Option<OverlayOptions>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .overlay(
+                &other.inner,
+                x.unwrap_or_default(),
+                y.unwrap_or_default(),
+                opacity.unwrap_or(1.0) as f32,
+                options.map(Into::into),
+            )
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 添加图片水印
+    ///
+    /// # 参数
+    /// - `mark`: 水印图片
+    /// - `options`: 水印选项，默认右下角、边距 16、不透明度 0.5
+    #[napi]
+    pub fn watermark(&self, mark: &Image, options: Option<WatermarkOptions>) -> Result<Self> {
+        let mark = piccy_core::Watermark::Image(mark.inner.clone());
+        let inner = self
+            .inner
+            .watermark(&mark, options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+
+    /// 添加文字水印
+    ///
+    /// # 参数
+    /// - `mark`: 文字水印
+    /// - `options`: 水印选项，默认右下角、边距 16、不透明度 0.5
+    #[napi]
+    pub fn watermark_text(
+        &self,
+        mark: TextWatermark,
+        options: Option<WatermarkOptions>,
+    ) -> Result<Self> {
+        let inner = self
+            .inner
+            .watermark(&mark.into(), options.map(Into::into))
+            .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+        Ok(Self { inner })
+    }
+}
+
+/// 设置并行线程数
+///
+/// 该设置作用于整个进程。
+///
+/// # 参数
+/// - `threads`: 线程数，为 0 或不传时使用全局线程池，为 1 时严格单线程
+#[napi]
+pub fn set_threads(threads: Option<u32>) -> Result<()> {
+    let parallelism = piccy_core::Parallelism::threads(threads.unwrap_or(0) as usize)
+        .map_err(|e| napi::Error::from_reason(e.to_string()))?;
+    piccy_core::set_parallelism(parallelism);
+    Ok(())
 }
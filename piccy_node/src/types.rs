@@ -1,3 +1,4 @@
+use napi::bindgen_prelude::Buffer;
 use napi_derive::napi;
 use serde::{Deserialize, Serialize};
 
@@ -39,6 +40,12 @@ pub enum FlipMode {
     Horizontal,
     /// 垂直翻转
     Vertical,
+    /// 同时水平和垂直翻转
+    Both,
+    /// 沿主对角线翻转
+    Transpose,
+    /// 沿副对角线翻转
+    Transverse,
 }
 
 impl From<FlipMode> for piccy_core::FlipMode {
@@ -46,6 +53,9 @@ impl From<FlipMode> for piccy_core::FlipMode {
         match mode {
             FlipMode::Horizontal => piccy_core::FlipMode::Horizontal,
             FlipMode::Vertical => piccy_core::FlipMode::Vertical,
+            FlipMode::Both => piccy_core::FlipMode::Both,
+            FlipMode::Transpose => piccy_core::FlipMode::Transpose,
+            FlipMode::Transverse => piccy_core::FlipMode::Transverse,
         }
     }
 }
@@ -58,6 +68,8 @@ pub enum MergeMode {
     Horizontal,
     /// 垂直拼接
     Vertical,
+    /// 网格拼接，列数由拼接选项的 `columns` 指定
+    Grid,
 }
 
 impl From<MergeMode> for piccy_core::MergeMode {
@@ -65,6 +77,7 @@ impl From<MergeMode> for piccy_core::MergeMode {
         match mode {
             MergeMode::Horizontal => piccy_core::MergeMode::Horizontal,
             MergeMode::Vertical => piccy_core::MergeMode::Vertical,
+            MergeMode::Grid => piccy_core::MergeMode::Grid { columns: None },
         }
     }
 }
@@ -101,3 +114,477 @@ impl From<Rgb> for image::Rgb<u8> {
         Self::from([value.r, value.g, value.b])
     }
 }
+
+#[napi(object)]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<Rgba> for image::Rgba<u8> {
+    fn from(value: Rgba) -> Self {
+        Self::from([value.r, value.g, value.b, value.a])
+    }
+}
+
+/// 二维坐标点
+#[napi(object)]
+pub struct Point {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// 将四个坐标点转换为四边形顶点
+pub(crate) fn quad(points: Vec<Point>) -> napi::Result<[(f32, f32); 4]> {
+    let points: [Point; 4] = points
+        .try_into()
+        .map_err(|_| napi::Error::from_reason("quad must have 4 points"))?;
+    Ok(points.map(|point| (point.x as f32, point.y as f32)))
+}
+
+/// 锚点位置
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum Gravity {
+    /// 居中
+    Center,
+    /// 顶部居中
+    Top,
+    /// 底部居中
+    Bottom,
+    /// 左侧居中
+    Left,
+    /// 右侧居中
+    Right,
+    /// 左上角
+    TopLeft,
+    /// 右上角
+    TopRight,
+    /// 左下角
+    BottomLeft,
+    /// 右下角
+    BottomRight,
+}
+
+impl From<Gravity> for piccy_core::Gravity {
+    fn from(gravity: Gravity) -> Self {
+        match gravity {
+            Gravity::Center => piccy_core::Gravity::Center,
+            Gravity::Top => piccy_core::Gravity::Top,
+            Gravity::Bottom => piccy_core::Gravity::Bottom,
+            Gravity::Left => piccy_core::Gravity::Left,
+            Gravity::Right => piccy_core::Gravity::Right,
+            Gravity::TopLeft => piccy_core::Gravity::TopLeft,
+            Gravity::TopRight => piccy_core::Gravity::TopRight,
+            Gravity::BottomLeft => piccy_core::Gravity::BottomLeft,
+            Gravity::BottomRight => piccy_core::Gravity::BottomRight,
+        }
+    }
+}
+
+/// 缩放适配模式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum ResizeFit {
+    /// 拉伸到目标尺寸，不保持宽高比
+    Fill,
+    /// 保持宽高比铺满目标尺寸，超出部分按锚点裁剪
+    Cover,
+    /// 保持宽高比完整放入目标尺寸，空白部分使用背景色填充
+    Contain,
+    /// 保持宽高比缩放到不超过目标尺寸
+    Inside,
+    /// 保持宽高比缩放到不小于目标尺寸
+    Outside,
+}
+
+impl From<ResizeFit> for piccy_core::ResizeFit {
+    fn from(fit: ResizeFit) -> Self {
+        match fit {
+            ResizeFit::Fill => piccy_core::ResizeFit::Fill,
+            ResizeFit::Cover => piccy_core::ResizeFit::Cover,
+            ResizeFit::Contain => piccy_core::ResizeFit::Contain,
+            ResizeFit::Inside => piccy_core::ResizeFit::Inside,
+            ResizeFit::Outside => piccy_core::ResizeFit::Outside,
+        }
+    }
+}
+
+/// 重采样滤波器
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum ResampleFilter {
+    /// 最近邻，适合像素画
+    Nearest,
+    /// 双线性
+    Bilinear,
+    /// 双三次（Catmull-Rom）
+    CatmullRom,
+    /// 高斯
+    Gaussian,
+    /// Lanczos3
+    Lanczos3,
+}
+
+impl From<ResampleFilter> for piccy_core::ResampleFilter {
+    fn from(filter: ResampleFilter) -> Self {
+        match filter {
+            ResampleFilter::Nearest => piccy_core::ResampleFilter::Nearest,
+            ResampleFilter::Bilinear => piccy_core::ResampleFilter::Bilinear,
+            ResampleFilter::CatmullRom => piccy_core::ResampleFilter::CatmullRom,
+            ResampleFilter::Gaussian => piccy_core::ResampleFilter::Gaussian,
+            ResampleFilter::Lanczos3 => piccy_core::ResampleFilter::Lanczos3,
+        }
+    }
+}
+
+/// 缩放选项
+#[napi(object)]
+pub struct ResizeOptions {
+    /// 适配模式，默认拉伸
+    pub fit: Option<ResizeFit>,
+    /// 裁剪或留白时的锚点，默认居中
+    pub gravity: Option<Gravity>,
+    /// 留白部分的背景色，默认透明
+    pub background: Option<Rgba>,
+    /// 重采样滤波器，默认 Lanczos3
+    pub filter: Option<ResampleFilter>,
+}
+
+impl From<ResizeOptions> for piccy_core::ResizeOptions {
+    fn from(options: ResizeOptions) -> Self {
+        let default = piccy_core::ResizeOptions::default();
+        Self {
+            fit: options.fit.map(Into::into).unwrap_or(default.fit),
+            gravity: options.gravity.map(Into::into).unwrap_or(default.gravity),
+            background: options
+                .background
+                .map(Into::into)
+                .unwrap_or(default.background),
+            filter: options.filter.map(Into::into).unwrap_or(default.filter),
+        }
+    }
+}
+
+/// 插值方式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum Interpolation {
+    /// 最近邻
+    Nearest,
+    /// 双线性
+    Bilinear,
+    /// 双三次
+    Bicubic,
+}
+
+impl From<Interpolation> for piccy_core::Interpolation {
+    fn from(interpolation: Interpolation) -> Self {
+        match interpolation {
+            Interpolation::Nearest => piccy_core::Interpolation::Nearest,
+            Interpolation::Bilinear => piccy_core::Interpolation::Bilinear,
+            Interpolation::Bicubic => piccy_core::Interpolation::Bicubic,
+        }
+    }
+}
+
+/// 画布空白部分的填充方式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum FillMode {
+    /// 透明
+    Transparent,
+    /// 纯色，颜色由 `fillColor` 指定
+    Color,
+    /// 延伸边缘像素
+    Edge,
+    /// 镜像边缘像素
+    Mirror,
+}
+
+/// 组合填充方式与填充颜色
+///
+/// 只指定颜色时使用纯色填充。
+pub(crate) fn fill(mode: Option<FillMode>, color: Option<Rgba>) -> piccy_core::Fill {
+    let color: Option<image::Rgba<u8>> = color.map(Into::into);
+    match (mode, color) {
+        (Some(FillMode::Transparent), _) => piccy_core::Fill::Transparent,
+        (Some(FillMode::Edge), _) => piccy_core::Fill::Edge,
+        (Some(FillMode::Mirror), _) => piccy_core::Fill::Mirror,
+        (Some(FillMode::Color) | None, Some(color)) => piccy_core::Fill::Color(color),
+        (Some(FillMode::Color), None) => piccy_core::Fill::Color(image::Rgba([0, 0, 0, 255])),
+        (None, None) => piccy_core::Fill::default(),
+    }
+}
+
+/// 几何变换选项
+#[napi(object)]
+pub struct TransformOptions {
+    /// 是否扩展画布以容纳完整的变换结果，默认保持原画布尺寸
+    pub expand: Option<bool>,
+    /// 插值方式，默认双线性
+    pub interpolation: Option<Interpolation>,
+    /// 空白部分的填充方式，默认透明
+    pub fill: Option<FillMode>,
+    /// 纯色填充时使用的颜色，默认黑色
+    pub fill_color: Option<Rgba>,
+}
+
+impl From<TransformOptions> for piccy_core::TransformOptions {
+    fn from(options: TransformOptions) -> Self {
+        let default = piccy_core::TransformOptions::default();
+        Self {
+            expand: options.expand.unwrap_or(default.expand),
+            interpolation: options
+                .interpolation
+                .map(Into::into)
+                .unwrap_or(default.interpolation),
+            fill: fill(options.fill, options.fill_color),
+        }
+    }
+}
+
+/// 混合模式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum BlendMode {
+    /// 正常
+    Normal,
+    /// 正片叠底
+    Multiply,
+    /// 滤色
+    Screen,
+    /// 叠加
+    Overlay,
+    /// 变暗
+    Darken,
+    /// 变亮
+    Lighten,
+    /// 颜色减淡
+    ColorDodge,
+    /// 颜色加深
+    ColorBurn,
+    /// 柔光
+    SoftLight,
+    /// 强光
+    HardLight,
+    /// 差值
+    Difference,
+    /// 排除
+    Exclusion,
+    /// 色相
+    Hue,
+    /// 饱和度
+    Saturation,
+    /// 颜色
+    Color,
+    /// 明度
+    Luminosity,
+}
+
+impl From<BlendMode> for piccy_core::BlendMode {
+    fn from(mode: BlendMode) -> Self {
+        match mode {
+            BlendMode::Normal => piccy_core::BlendMode::Normal,
+            BlendMode::Multiply => piccy_core::BlendMode::Multiply,
+            BlendMode::Screen => piccy_core::BlendMode::Screen,
+            BlendMode::Overlay => piccy_core::BlendMode::Overlay,
+            BlendMode::Darken => piccy_core::BlendMode::Darken,
+            BlendMode::Lighten => piccy_core::BlendMode::Lighten,
+            BlendMode::ColorDodge => piccy_core::BlendMode::ColorDodge,
+            BlendMode::ColorBurn => piccy_core::BlendMode::ColorBurn,
+            BlendMode::SoftLight => piccy_core::BlendMode::SoftLight,
+            BlendMode::HardLight => piccy_core::BlendMode::HardLight,
+            BlendMode::Difference => piccy_core::BlendMode::Difference,
+            BlendMode::Exclusion => piccy_core::BlendMode::Exclusion,
+            BlendMode::Hue => piccy_core::BlendMode::Hue,
+            BlendMode::Saturation => piccy_core::BlendMode::Saturation,
+            BlendMode::Color => piccy_core::BlendMode::Color,
+            BlendMode::Luminosity => piccy_core::BlendMode::Luminosity,
+        }
+    }
+}
+
+/// 蒙版通道
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum MaskChannel {
+    /// 透明度通道
+    Alpha,
+    /// 亮度
+    Luminance,
+    /// 红色通道
+    Red,
+    /// 绿色通道
+    Green,
+    /// 蓝色通道
+    Blue,
+}
+
+impl From<MaskChannel> for piccy_core::MaskChannel {
+    fn from(channel: MaskChannel) -> Self {
+        match channel {
+            MaskChannel::Alpha => piccy_core::MaskChannel::Alpha,
+            MaskChannel::Luminance => piccy_core::MaskChannel::Luminance,
+            MaskChannel::Red => piccy_core::MaskChannel::Red,
+            MaskChannel::Green => piccy_core::MaskChannel::Green,
+            MaskChannel::Blue => piccy_core::MaskChannel::Blue,
+        }
+    }
+}
+
+/// 发光模式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum GlowMode {
+    /// 外发光
+    Outer,
+    /// 内发光
+    Inner,
+}
+
+impl From<GlowMode> for piccy_core::GlowMode {
+    fn from(mode: GlowMode) -> Self {
+        match mode {
+            GlowMode::Outer => piccy_core::GlowMode::Outer,
+            GlowMode::Inner => piccy_core::GlowMode::Inner,
+        }
+    }
+}
+
+/// 拼接时在交叉轴上的对齐方式
+#[derive(Debug, Copy, Clone)]
+#[napi]
+pub enum Align {
+    /// 顶部或左侧对齐
+    Start,
+    /// 居中对齐
+    Center,
+    /// 底部或右侧对齐
+    End,
+}
+
+impl From<Align> for piccy_core::Align {
+    fn from(align: Align) -> Self {
+        match align {
+            Align::Start => piccy_core::Align::Start,
+            Align::Center => piccy_core::Align::Center,
+            Align::End => piccy_core::Align::End,
+        }
+    }
+}
+
+/// 拼接选项
+#[napi(object)]
+pub struct MergeOptions {
+    /// 图片之间的间距，默认 0
+    pub spacing: Option<u32>,
+    /// 背景色，默认透明
+    pub background: Option<Rgba>,
+    /// 交叉轴上的对齐方式，默认居中
+    pub align: Option<Align>,
+    /// 是否缩放到统一尺寸，默认缩放
+    pub scale: Option<bool>,
+    /// 缩放时使用的重采样滤波器，默认双线性
+    pub filter: Option<ResampleFilter>,
+    /// 网格拼接的列数，不传时排成近似正方形
+    pub columns: Option<u32>,
+}
+
+impl From<MergeOptions> for piccy_core::MergeOptions {
+    fn from(options: MergeOptions) -> Self {
+        let default = piccy_core::MergeOptions::default();
+        Self {
+            spacing: options.spacing.unwrap_or(default.spacing),
+            background: options
+                .background
+                .map(Into::into)
+                .unwrap_or(default.background),
+            align: options.align.map(Into::into).unwrap_or(default.align),
+            scale: options.scale.unwrap_or(default.scale),
+            filter: options.filter.map(Into::into).unwrap_or(default.filter),
+        }
+    }
+}
+
+/// 叠加选项
+#[napi(object)]
+pub struct OverlayOptions {
+    /// 偏移量的参照锚点，默认左上角
+    pub anchor: Option<Gravity>,
+    /// 混合模式，默认正常
+    pub blend: Option<BlendMode>,
+}
+
+impl From<OverlayOptions> for piccy_core::OverlayOptions {
+    fn from(options: OverlayOptions) -> Self {
+        let default = piccy_core::OverlayOptions::default();
+        Self {
+            anchor: options.anchor.map(Into::into).unwrap_or(default.anchor),
+            blend: options.blend.map(Into::into).unwrap_or(default.blend),
+        }
+    }
+}
+
+/// 文字水印
+#[napi(object)]
+pub struct TextWatermark {
+    /// 文字内容，不支持换行
+    pub text: String,
+    /// TrueType 或 OpenType 字体文件数据
+    pub font: Buffer,
+    /// 字号，单位为像素，默认 32
+    pub size: Option<f64>,
+    /// 文字颜色，默认白色
+    pub color: Option<Rgba>,
+}
+
+impl From<TextWatermark> for piccy_core::Watermark {
+    fn from(mark: TextWatermark) -> Self {
+        Self::Text {
+            text: mark.text,
+            font: mark.font.to_vec().into(),
+            size: mark.size.unwrap_or(32.0) as f32,
+            color: mark
+                .color
+                .map(Into::into)
+                .unwrap_or(image::Rgba([255, 255, 255, 255])),
+        }
+    }
+}
+
+/// 水印选项
+#[napi(object)]
+pub struct WatermarkOptions {
+    /// 水印位置，默认右下角，平铺时忽略
+    pub position: Option<Gravity>,
+    /// 水印与图像边缘的距离，平铺时为水印之间的间距，默认 16
+    pub margin: Option<u32>,
+    /// 水印的不透明度，取值 0 ~ 1，默认 0.5
+    pub opacity: Option<f64>,
+    /// 水印宽度占图像宽度的比例，默认保持水印原尺寸
+    pub scale: Option<f64>,
+    /// 是否沿对角线平铺水印
+    pub tiled: Option<bool>,
+    /// 缩放水印时使用的重采样滤波器，默认 Lanczos3
+    pub filter: Option<ResampleFilter>,
+}
+
+impl From<WatermarkOptions> for piccy_core::WatermarkOptions {
+    fn from(options: WatermarkOptions) -> Self {
+        let default = piccy_core::WatermarkOptions::default();
+        Self {
+            position: options.position.map(Into::into).unwrap_or(default.position),
+            margin: options.margin.unwrap_or(default.margin),
+            opacity: options
+                .opacity
+                .map(|opacity| opacity as f32)
+                .unwrap_or(default.opacity),
+            scale: options.scale.map(|scale| scale as f32).or(default.scale),
+            tiled: options.tiled.unwrap_or(default.tiled),
+            filter: options.filter.map(Into::into).unwrap_or(default.filter),
+        }
+    }
+}